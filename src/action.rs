@@ -41,6 +41,9 @@ pub enum TuiAction {
   Notify(NotificationEnum),
   Status(String),
   Reset,
+  /// Shows text set from outside the input, e.g. by an editor over RPC, in the search input.
+  SetSearchInput(String),
+  SetReplaceInput(String),
 }
 
 impl<'de> Deserialize<'de> for AppAction {
@@ -107,6 +110,8 @@ impl<'de> Deserialize<'de> for AppAction {
           })),
           // Redux Thunk Actions
           "ProcessReplace" => Ok(AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(false)))),
+          "ExportChangeSet" => Ok(AppAction::Thunk(ThunkAction::ExportChangeSet)),
+          "ExportQuickfix" => Ok(AppAction::Thunk(ThunkAction::ExportQuickfix)),
           _ => Err(E::custom(format!("Unknown Action variant: {value}"))),
         }
       }
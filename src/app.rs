@@ -5,11 +5,12 @@ use std::{
   sync::Arc,
 };
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result};
 use crossterm::event::KeyEvent;
+use encoding_rs::Encoding;
 use ratatui::prelude::Rect;
 use redux_rs::{
-  middlewares::thunk::{self, ThunkMiddleware},
+  middlewares::thunk::{self, Thunk, ThunkMiddleware},
   Selector, Store, StoreApi,
 };
 use serde::{Deserialize, Serialize};
@@ -19,12 +20,14 @@ use tokio::sync::mpsc;
 use crate::{
   action::{AppAction, TuiAction},
   components::{
+    confirm_dirty_files_dialog::ConfirmDirtyFilesDialog,
     confirm_empty_replace_dialog::ConfirmEmptyReplaceDialog,
     confirm_git_dir_dialog::ConfirmGitDirDialog,
     help_dialog::HelpDialog,
     notifications::{NotificationEnum, Notifications},
     preview::Preview,
     replace::Replace,
+    replace_summary_dialog::ReplaceSummaryDialog,
     search::Search,
     search_result::SearchResult,
     small_help::SmallHelp,
@@ -32,19 +35,44 @@ use crate::{
     Component,
   },
   config::Config,
+  encoding, json_output,
   mode::Mode,
+  quickfix,
   redux::{
     action::Action,
     reducer::reducer,
-    state::State,
-    thunk::{thunk_impl, ThunkAction},
+    state::{AstGrepState, GitState, ReplaceTextKind, SearchTextKind, State},
+    thunk::{process_search::ProcessSearchThunk, thunk_impl, ThunkAction},
   },
+  ripgrep,
+  rpc::RpcServer,
+  session::Session,
   tabs::Tab,
-  tui,
+  tui, watcher,
 };
 
 const FILE_COUNT_THRESHOLD: usize = 1000;
 
+/// Command line flags tuning how the app runs, merged with their config file settings where those exist.
+#[derive(Default, Debug, Clone)]
+pub struct AppOptions {
+  pub transactional_replace: bool,
+  pub encoding: Option<String>,
+  pub watch: bool,
+  pub resume: bool,
+  pub query: QueryOptions,
+  pub listen: Option<PathBuf>,
+}
+
+/// Search and replace given on the command line, each part overriding the resumed session's.
+#[derive(Default, Debug, Clone)]
+pub struct QueryOptions {
+  pub search_text: Option<String>,
+  pub search_kind: Option<SearchTextKind>,
+  pub replace_text: Option<String>,
+  pub replace_kind: Option<ReplaceTextKind>,
+}
+
 pub struct App {
   pub config: Config,
   pub tick_rate: f64,
@@ -54,22 +82,56 @@ pub struct App {
   pub should_suspend: bool,
   pub mode: Mode,
   pub last_tick_key_events: Vec<KeyEvent>,
-  pub project_root: PathBuf,
+  pub project_roots: Vec<PathBuf>,
+  pub search_files: Option<Vec<PathBuf>>,
+  pub ast_grep: AstGrepState,
+  pub git: GitState,
+  pub transactional_replace: bool,
+  pub encoding: Option<&'static Encoding>,
+  pub watch: bool,
+  pub resume: bool,
+  /// The session `--resume` restores, None when there was no saved one.
+  pub session: Option<Session>,
+  pub query: QueryOptions,
+  /// Socket editors send requests to, see `rpc`.
+  pub listen: Option<PathBuf>,
 }
 
 impl App {
-  pub fn new(project_root: PathBuf) -> Result<Self> {
+  pub fn new(
+    project_roots: Vec<PathBuf>,
+    search_files: Option<Vec<PathBuf>>,
+    ast_grep: AstGrepState,
+    git: GitState,
+    options: AppOptions,
+  ) -> Result<Self> {
+    let AppOptions { transactional_replace, encoding, watch, resume, query, listen } = options;
     let config = Config::new()?;
+    let session = if resume {
+      Session::load(&project_roots).map_err(|e| eyre!("Failed to read the saved session: {e}"))?
+    } else {
+      None
+    };
+    let encoding = match encoding.as_ref().or(config.encoding.as_ref()) {
+      Some(label) => encoding::parse_encoding(label).map_err(|e| eyre!(e))?,
+      None => None,
+    };
     let mode = Mode::Normal;
 
-    let search = Search::new();
-    let replace = Replace::new();
+    let search_text =
+      query.search_text.as_deref().or(session.as_ref().map(|session| session.search_text.text.as_str()));
+    let replace_text =
+      query.replace_text.as_deref().or(session.as_ref().map(|session| session.replace_text.text.as_str()));
+    let search = Search::with_text(search_text.unwrap_or_default());
+    let replace = Replace::with_text(replace_text.unwrap_or_default());
     let search_result = SearchResult::new();
     let preview = Preview::new();
     let notification = Notifications::new();
     let small_help = SmallHelp::default();
     let confirm_git_dir_dialog = ConfirmGitDirDialog::default();
     let confirm_empty_replace_dialog = ConfirmEmptyReplaceDialog::default();
+    let confirm_dirty_files_dialog = ConfirmDirtyFilesDialog::new();
+    let replace_summary_dialog = ReplaceSummaryDialog::new();
     let help_dialog = HelpDialog::new();
     let status = Status::default();
     Ok(Self {
@@ -85,6 +147,8 @@ impl App {
         Box::new(status),
         Box::new(confirm_git_dir_dialog),
         Box::new(confirm_empty_replace_dialog),
+        Box::new(confirm_dirty_files_dialog),
+        Box::new(replace_summary_dialog),
         Box::new(help_dialog),
       ],
       should_quit: false,
@@ -92,35 +156,138 @@ impl App {
       config,
       mode,
       last_tick_key_events: Vec::new(),
-      project_root,
+      project_roots,
+      search_files,
+      ast_grep,
+      git,
+      transactional_replace,
+      encoding,
+      watch,
+      resume,
+      session,
+      query,
+      listen,
     })
   }
 
-  fn is_large_folder(path: &Path) -> bool {
-    let output =
-      Command::new("rg").args(["--files", "--count-matches", "--max-count", "1", path.to_str().unwrap_or("")]).output();
-
-    match output {
-      Ok(output) => {
-        if output.status.success() {
-          let file_count = String::from_utf8_lossy(&output.stdout).lines().count();
-          file_count > FILE_COUNT_THRESHOLD
-        } else {
-          log::error!("ripgrep command failed: {}", String::from_utf8_lossy(&output.stderr));
-          false
-        }
+  /// State to start from: the command line options merged with the config file, the resumed session and the query
+  /// given on the command line.
+  pub fn initial_state(&self) -> State {
+    let state = State {
+      search_files: self.search_files.clone(),
+      ast_grep: self.ast_grep.clone(),
+      git: GitState {
+        auto_commit: self.git.auto_commit || self.config.auto_commit || self.config.commit_branch.is_some(),
+        commit_branch: self.git.commit_branch.clone().or_else(|| self.config.commit_branch.clone()),
+        ..self.git.clone()
       },
-      Err(e) => {
-        log::error!("Failed to execute ripgrep: {}", e);
-        false
+      search_engine: self.config.search_engine.clone(),
+      search_backends: self.config.search_backends.clone(),
+      transactional_replace: self.transactional_replace || self.config.transactional_replace,
+      encoding: self.encoding,
+      ..State::new(self.project_roots.clone())
+    };
+    let mut state = match self.session.clone() {
+      Some(session) => session.restore(state),
+      None => state,
+    };
+
+    let query = &self.query;
+    if let Some(text) = &query.search_text {
+      state.search_text.text = text.clone();
+    }
+    if let Some(kind) = query.search_kind {
+      state.search_text.kind = kind;
+    }
+    if let Some(text) = &query.replace_text {
+      state.replace_text.text = text.clone();
+    }
+    if let Some(kind) = query.replace_kind {
+      state.replace_text.kind = kind;
+    }
+
+    // A rule file given on the command line starts in AST grep mode, searching with the rule right away.
+    #[cfg(feature = "ast_grep")]
+    if state.ast_grep.rule_file.is_some() || state.search_text.kind == SearchTextKind::AstGrep {
+      state.search_text.kind = SearchTextKind::AstGrep;
+      state.replace_text.kind = ReplaceTextKind::AstGrep;
+    }
+    state
+  }
+
+  /// Runs the search without the interface and prints its results to stdout as JSON Lines, see `json_output`.
+  pub async fn print_json(&mut self) -> Result<()> {
+    let state = self.search_headless("--json").await?;
+    json_output::write_json_lines(&state, &mut std::io::stdout().lock())?;
+    Ok(())
+  }
+
+  /// Runs the search without the interface and writes its matches as a quickfix list to `quickfix_file`, stdout for
+  /// `-`. With `edit` the editor opens on the list, a temporary file when no file is given.
+  pub async fn write_quickfix(&mut self, quickfix_file: Option<&Path>, edit: bool) -> Result<()> {
+    let option = if quickfix_file.is_some() { "--quickfix" } else { "--edit" };
+    let state = self.search_headless(option).await?;
+    let (quickfix_file, is_temporary) = match quickfix_file {
+      Some(path) if path.as_os_str() == "-" => {
+        if edit {
+          return Err(eyre!("--edit needs a quickfix file, not stdout"));
+        }
+        return Ok(quickfix::write_quickfix(&state, &mut std::io::stdout().lock())?);
       },
+      Some(path) => (path.to_path_buf(), false),
+      None => (std::env::temp_dir().join(format!("serpl-{}.qf", std::process::id())), true),
+    };
+
+    quickfix::write_quickfix(&state, &mut fs::File::create(&quickfix_file)?)?;
+    if edit {
+      let opened = quickfix::open_editor(&quickfix_file);
+      if is_temporary {
+        let _ = fs::remove_file(&quickfix_file);
+      }
+      opened.map_err(|e| eyre!("Failed to open the editor: {}", e))?;
+    }
+    Ok(())
+  }
+
+  /// Runs the search of the initial state to completion without the interface, failing with the errors it reported.
+  async fn search_headless(&self, option: &str) -> Result<State> {
+    let state = self.initial_state();
+    if !state.has_search_query() {
+      return Err(eyre!("{option} needs a search text, give it with --search"));
+    }
+
+    let store = Arc::new(Store::new_with_state(reducer, state));
+    let (action_tx, mut action_rx) = mpsc::unbounded_channel();
+    ProcessSearchThunk::new(Arc::new(action_tx)).execute(store.clone()).await;
+    let mut errors = Vec::new();
+    while let Ok(action) = action_rx.try_recv() {
+      if let AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(message))) = action {
+        errors.push(message);
+      }
     }
+    if !errors.is_empty() {
+      return Err(eyre!(errors.join("\n")));
+    }
+    Ok(store.state_cloned().await)
+  }
+
+  /// Saves the query and the curated results for `--resume`, failing only costs the next resume.
+  fn save_session(&self, state: &State) {
+    if let Err(e) = Session::from_state(state).save(&self.project_roots) {
+      log::error!("Failed to save the session: {}", e);
+    }
+  }
+
+  fn is_large_folder(paths: &[PathBuf]) -> bool {
+    if paths.is_empty() {
+      return false;
+    }
+    ripgrep::native::list_files(paths).len() > FILE_COUNT_THRESHOLD
   }
 
   pub async fn run(&mut self) -> Result<()> {
     log::info!("Starting app..");
-    let initial_state = State::new(self.project_root.clone());
-    let mut state = initial_state.clone();
+    let mut state = self.initial_state();
 
     let (action_tx, mut action_rx) = mpsc::unbounded_channel();
     let (redux_action_tx, mut redux_action_rx) = mpsc::unbounded_channel::<AppAction>();
@@ -136,7 +303,7 @@ impl App {
     }
 
     // handle big folders
-    let is_large_folder = Self::is_large_folder(&self.project_root);
+    let is_large_folder = Self::is_large_folder(&state.search_paths());
     state.is_large_folder = is_large_folder;
     let store = Store::new_with_state(reducer, state).wrap(ThunkMiddleware).await;
     if is_large_folder {
@@ -154,8 +321,60 @@ impl App {
       component.register_config_handler(self.config.clone())?;
     }
 
+    // Dropping the watcher stops watching, so it lives as long as the loop below.
+    let _watcher = if self.watch || self.config.watch {
+      match watcher::watch(&self.project_roots, redux_action_tx.clone()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+          log::error!("Failed to watch the project roots: {}", e);
+          let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(format!(
+            "Failed to watch for file changes: {e}"
+          ))));
+          action_tx.send(error_action)?;
+          None
+        },
+      }
+    } else {
+      None
+    };
+
+    // Dropping the server removes its socket.
+    let mut rpc_server = match &self.listen {
+      Some(path) => {
+        match RpcServer::listen(path, redux_action_tx.clone()) {
+          Ok(server) => Some(server),
+          Err(e) => {
+            log::error!("Failed to listen on {}: {}", path.display(), e);
+            let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(format!(
+              "Failed to listen on {}: {e}",
+              path.display()
+            ))));
+            action_tx.send(error_action)?;
+            None
+          },
+        }
+      },
+      None => None,
+    };
+
+    if self.resume && self.session.is_none() {
+      let no_session_action =
+        AppAction::Tui(TuiAction::Notify(NotificationEnum::Info("No saved session for this project".to_string())));
+      action_tx.send(no_session_action)?;
+    }
+
+    // A query from the command line or a resumed session is searched right away, like a rule file.
+    let has_initial_query = self.session.is_some() || self.query.search_text.is_some();
+    let is_initial_search = has_initial_query && store.select(|state: &State| state.has_search_query()).await;
+    if is_initial_search || store.select(|state: &State| state.uses_ast_grep_rule()).await {
+      redux_action_tx.send(AppAction::Thunk(ThunkAction::ProcessSearch))?;
+    }
+
     loop {
       let state = store.state_cloned().await;
+      if let Some(rpc_server) = &mut rpc_server {
+        rpc_server.publish_state(&state);
+      }
       if let Some(e) = tui.next().await {
         match e {
           tui::Event::Quit => action_tx.send(AppAction::Tui(TuiAction::Quit))?,
@@ -202,8 +421,14 @@ impl App {
           AppAction::Tui(TuiAction::Tick) => {
             self.last_tick_key_events.drain(..);
           },
-          AppAction::Tui(TuiAction::Quit) => self.should_quit = true,
-          AppAction::Tui(TuiAction::Suspend) => self.should_suspend = true,
+          AppAction::Tui(TuiAction::Quit) => {
+            self.save_session(&state);
+            self.should_quit = true;
+          },
+          AppAction::Tui(TuiAction::Suspend) => {
+            self.save_session(&state);
+            self.should_suspend = true;
+          },
           AppAction::Tui(TuiAction::Resume) => self.should_suspend = false,
           AppAction::Tui(TuiAction::Resize(w, h)) => {
             tui.resize(Rect::new(0, 0, w, h))?;
@@ -216,18 +441,16 @@ impl App {
               }
             })?;
           },
-          AppAction::Tui(TuiAction::Render) => {
-            if !rendered {
-              rendered = true;
-              tui.draw(|f| {
-                for component in self.components.iter_mut() {
-                  let r = component.draw(f, f.area(), &state);
-                  if let Err(e) = r {
-                    action_tx.send(AppAction::Tui(TuiAction::Error(format!("Failed to draw: {e:?}")))).unwrap();
-                  }
+          AppAction::Tui(TuiAction::Render) if !rendered => {
+            rendered = true;
+            tui.draw(|f| {
+              for component in self.components.iter_mut() {
+                let r = component.draw(f, f.area(), &state);
+                if let Err(e) = r {
+                  action_tx.send(AppAction::Tui(TuiAction::Error(format!("Failed to draw: {e:?}")))).unwrap();
                 }
-              })?;
-            }
+              }
+            })?;
           },
           _ => {},
         }
@@ -2,6 +2,49 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::redux::state::MetaVariableBinding;
+
+#[cfg(feature = "ast_grep_native")]
+pub mod native;
+
+/// Languages offered by the language picker, in the names ast-grep accepts for `--lang`.
+pub const LANGUAGES: &[&str] = &[
+  "bash",
+  "c",
+  "cpp",
+  "csharp",
+  "css",
+  "elixir",
+  "go",
+  "haskell",
+  "html",
+  "java",
+  "javascript",
+  "json",
+  "kotlin",
+  "lua",
+  "php",
+  "python",
+  "ruby",
+  "rust",
+  "scala",
+  "swift",
+  "tsx",
+  "typescript",
+  "yaml",
+];
+
+/// Language following `current` in the picker, going back to per file inference after the last one.
+pub fn next_language(current: Option<&str>) -> Option<String> {
+  match current {
+    None => LANGUAGES.first().map(|lang| lang.to_string()),
+    Some(current) => {
+      let position = LANGUAGES.iter().position(|lang| *lang == current)?;
+      LANGUAGES.get(position + 1).map(|lang| lang.to_string())
+    },
+  }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct AstGrepOutput {
   pub text: String,
@@ -36,7 +79,7 @@ pub struct Position {
   pub column: usize,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ReplacementOffsets {
   pub start: usize,
   pub end: usize,
@@ -49,6 +92,55 @@ pub struct MetaVariables {
   pub transformed: HashMap<String, String>,
 }
 
+impl MetaVariables {
+  /// Flattens the captures into `$NAME`, `$$$NAME` and transformed bindings, each group sorted by name.
+  pub fn bindings(&self) -> Vec<MetaVariableBinding> {
+    let mut single: Vec<MetaVariableBinding> = self
+      .single
+      .iter()
+      .map(|(name, variable)| {
+        MetaVariableBinding {
+          name: format!("${name}"),
+          text: variable.text.clone(),
+          range: Some(variable.range.line_columns()),
+        }
+      })
+      .collect();
+    single.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut multi: Vec<MetaVariableBinding> = self
+      .multi
+      .iter()
+      .map(|(name, variables)| {
+        MetaVariableBinding {
+          name: format!("$$${name}"),
+          text: variables.iter().map(|variable| variable.text.as_str()).collect::<Vec<_>>().join(" "),
+          range: variables
+            .first()
+            .zip(variables.last())
+            .map(|(first, last)| (first.range.line_columns().0, last.range.line_columns().1)),
+        }
+      })
+      .collect();
+    multi.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut transformed: Vec<MetaVariableBinding> = self
+      .transformed
+      .iter()
+      .map(|(name, text)| MetaVariableBinding { name: format!("${name}"), text: text.clone(), range: None })
+      .collect();
+    transformed.sort_by(|a, b| a.name.cmp(&b.name));
+
+    single.into_iter().chain(multi).chain(transformed).collect()
+  }
+}
+
+impl Range {
+  fn line_columns(&self) -> ((usize, usize), (usize, usize)) {
+    ((self.start.line, self.start.column), (self.end.line, self.end.column))
+  }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct MetaVariable {
   pub text: String,
@@ -0,0 +1,205 @@
+use std::{collections::HashMap, fmt, fs, path::PathBuf};
+
+use ast_grep_core::{
+  tree_sitter::{LanguageExt, StrDoc},
+  Language, NodeMatch, Pattern, PatternError,
+};
+use ast_grep_language::SupportLang;
+
+use super::{AstGrepOutput, ByteOffset, MetaVariable, MetaVariables, Position, Range, ReplacementOffsets};
+
+#[derive(Debug)]
+pub enum NativeSearchError {
+  UnknownLanguage(String),
+  InvalidPattern(PatternError),
+}
+
+impl fmt::Display for NativeSearchError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NativeSearchError::UnknownLanguage(lang) => write!(f, "Unknown AST grep language: {lang}"),
+      NativeSearchError::InvalidPattern(e) => write!(f, "Invalid AST grep pattern: {e}"),
+    }
+  }
+}
+
+impl std::error::Error for NativeSearchError {
+}
+
+/// Structural search over `paths` without the ast-grep binary, producing the same output as `ast-grep run --json`.
+/// The language is inferred per file unless `lang` is given, files in other languages are skipped then.
+pub fn search(
+  paths: &[PathBuf],
+  pattern: &str,
+  replacement: Option<&str>,
+  lang: Option<&str>,
+) -> Result<Vec<AstGrepOutput>, NativeSearchError> {
+  search_files(crate::ripgrep::native::list_files(paths), pattern, replacement, lang)
+}
+
+fn search_files(
+  files: Vec<String>,
+  pattern: &str,
+  replacement: Option<&str>,
+  lang: Option<&str>,
+) -> Result<Vec<AstGrepOutput>, NativeSearchError> {
+  let forced_lang = match lang {
+    Some(lang) => Some(lang.parse::<SupportLang>().map_err(|_| NativeSearchError::UnknownLanguage(lang.to_string()))?),
+    None => None,
+  };
+
+  let mut patterns: HashMap<SupportLang, Result<Pattern, PatternError>> = HashMap::new();
+  let mut results = Vec::new();
+
+  for file in files {
+    let Some(file_lang) = SupportLang::from_path(&file) else {
+      continue;
+    };
+    if forced_lang.is_some_and(|forced_lang| forced_lang != file_lang) {
+      continue;
+    }
+
+    let Ok(compiled) = patterns.entry(file_lang).or_insert_with(|| Pattern::try_new(pattern, file_lang)) else {
+      continue;
+    };
+    let Ok(source) = fs::read_to_string(&file) else {
+      continue;
+    };
+
+    let root = file_lang.ast_grep(&source);
+    for node_match in root.root().find_all(&*compiled) {
+      results.push(to_output(&node_match, &source, &file, file_lang, replacement));
+    }
+  }
+
+  // A pattern that parses in none of the searched languages is reported instead of silently matching nothing.
+  if !patterns.values().any(Result::is_ok) {
+    let error = match forced_lang {
+      Some(lang) => patterns.remove(&lang).and_then(Result::err),
+      None => patterns.into_values().find_map(Result::err),
+    };
+    if let Some(error) = error {
+      return Err(NativeSearchError::InvalidPattern(error));
+    }
+  }
+
+  Ok(results)
+}
+
+fn to_output(
+  node_match: &NodeMatch<'_, StrDoc<SupportLang>>,
+  source: &str,
+  file: &str,
+  lang: SupportLang,
+  replacement: Option<&str>,
+) -> AstGrepOutput {
+  let byte_range = node_match.range();
+  let lines_start = source[..byte_range.start].rfind('\n').map_or(0, |i| i + 1);
+  let lines_end = source[byte_range.end..].find('\n').map_or(source.len(), |i| byte_range.end + i);
+
+  let (replacement, replacement_offsets) = match replacement {
+    Some(replacement) => {
+      let edit = node_match.replace_by(replacement);
+      let offsets = ReplacementOffsets { start: edit.position, end: edit.position + edit.deleted_length };
+      (Some(String::from_utf8_lossy(&edit.inserted_text).to_string()), Some(offsets))
+    },
+    None => (None, None),
+  };
+
+  AstGrepOutput {
+    text: node_match.text().to_string(),
+    range: node_range(node_match),
+    file: file.to_string(),
+    lines: source[lines_start..lines_end].to_string(),
+    replacement,
+    replacement_offsets,
+    language: lang.to_string(),
+    meta_variables: Some(meta_variables(node_match)),
+  }
+}
+
+fn node_range(node: &ast_grep_core::Node<'_, StrDoc<SupportLang>>) -> Range {
+  let byte_range = node.range();
+  let start = node.start_pos();
+  let end = node.end_pos();
+  Range {
+    byte_offset: ByteOffset { start: byte_range.start, end: byte_range.end },
+    start: Position { line: start.line(), column: start.column(node) },
+    end: Position { line: end.line(), column: end.column(node) },
+  }
+}
+
+fn meta_variables(node_match: &NodeMatch<'_, StrDoc<SupportLang>>) -> MetaVariables {
+  use ast_grep_core::meta_var::MetaVariable as Variable;
+
+  let env = node_match.get_env();
+  let mut single = HashMap::new();
+  let mut multi = HashMap::new();
+
+  for variable in env.get_matched_variables() {
+    match variable {
+      Variable::Capture(name, _) => {
+        if let Some(node) = env.get_match(&name) {
+          single.insert(name, MetaVariable { text: node.text().to_string(), range: node_range(node) });
+        }
+      },
+      Variable::MultiCapture(name) => {
+        let nodes = env.get_multiple_matches(&name);
+        let captures =
+          nodes.iter().map(|node| MetaVariable { text: node.text().to_string(), range: node_range(node) }).collect();
+        multi.insert(name, captures);
+      },
+      Variable::Dropped(_) | Variable::Multiple => {},
+    }
+  }
+
+  MetaVariables { single, multi, transformed: HashMap::new() }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use tempfile::TempDir;
+
+  use super::*;
+
+  fn write_source(dir: &TempDir, name: &str, content: &str) -> String {
+    let path = dir.path().join(name);
+    fs::write(&path, content).unwrap();
+    path.to_string_lossy().to_string()
+  }
+
+  #[test]
+  fn test_search_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_source(&dir, "search.rs", "fn main() {\n  let a = foo(1, 2);\n}\n");
+
+    let results = search_files(vec![file.clone()], "foo($A, $$$REST)", Some("bar($A)"), None).unwrap();
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result.text, "foo(1, 2)");
+    assert_eq!(result.lines, "  let a = foo(1, 2);");
+    assert_eq!((result.range.start.line, result.range.start.column), (1, 10));
+    assert_eq!(result.replacement.as_deref(), Some("bar(1)"));
+    assert_eq!(result.replacement_offsets.as_ref().map(|o| (o.start, o.end)), Some((22, 31)));
+
+    let meta_variables = result.meta_variables.as_ref().unwrap();
+    assert_eq!(meta_variables.single["A"].text, "1");
+    assert_eq!(meta_variables.multi["REST"].iter().map(|v| v.text.as_str()).collect::<Vec<_>>(), vec!["2"]);
+
+    let other_language = search_files(vec![file.clone()], "foo($A, $$$REST)", None, Some("python")).unwrap();
+    assert!(other_language.is_empty());
+  }
+
+  #[test]
+  fn test_search_files_reports_invalid_pattern() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = write_source(&dir, "invalid.rs", "fn main() {}\n");
+
+    assert!(matches!(search_files(vec![file.clone()], "$$$", None, None), Err(NativeSearchError::InvalidPattern(_))));
+    assert!(matches!(
+      search_files(vec![file.clone()], "foo", None, Some("klingon")),
+      Err(NativeSearchError::UnknownLanguage(_))
+    ));
+  }
+}
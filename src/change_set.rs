@@ -0,0 +1,326 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  encoding,
+  json_output::json_file,
+  redux::{
+    state::{
+      FileReplaceResult, ReplaceOutcome, ReplaceTextKind, ReplaceTextState, SearchResultKind, SearchTextState, State,
+    },
+    utils::{relative_path, root_for_path},
+  },
+  transaction::FileTransaction,
+};
+
+/// Where the interface exports the change set, in the working directory.
+pub const CHANGE_SET_FILE: &str = "serpl-changes.json";
+
+const VERSION: u32 = 1;
+
+/// Reason given for the files left untouched because another file of the change set can't be applied.
+const NOT_APPLIED: &str = "not applied, another file of the change set conflicts";
+
+/// The replacements of a search, exported to be reviewed and applied elsewhere with `--apply-changes`. Each file
+/// carries the hash of the content its edits were made against, a file that changed since is a conflict. File path
+/// matches are left out, renames can't be exported.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ChangeSet {
+  pub version: u32,
+  pub search_text: SearchTextState,
+  pub replace_text: ReplaceTextState,
+  pub files: Vec<FileChanges>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct FileChanges {
+  /// Index of the file's project root, in the order the roots are given.
+  #[serde(default)]
+  pub root: usize,
+  /// Relative to the project root.
+  pub path: String,
+  /// FNV-1a hash of the file's bytes, as 16 hex digits.
+  pub hash: String,
+  pub encoding: String,
+  /// Sorted by start, never overlapping.
+  pub edits: Vec<Edit>,
+}
+
+/// Replaces `original` at the byte range `start..end` of the file's text, decoded to UTF-8 without byte order mark.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Edit {
+  pub start: usize,
+  pub end: usize,
+  pub original: String,
+  pub replacement: String,
+}
+
+impl ChangeSet {
+  /// The replacements a replace of the listed results would make. Fails when a file can't be read or changed since
+  /// the search, as the matches wouldn't line up with its content.
+  pub fn from_state(state: &State) -> io::Result<Self> {
+    let mut files = Vec::new();
+    for result in state.search_result.list.iter().filter(|r| r.kind == SearchResultKind::Content && !r.removed) {
+      let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", result.path));
+      let bytes = fs::read(&result.path).map_err(with_path)?;
+      let (text, file_encoding) = encoding::decode(&bytes, state.encoding).map_err(with_path)?;
+      let changed = || with_path(io::Error::new(io::ErrorKind::InvalidData, "changed since the search, search again"));
+
+      // Matches are placed by line in the decoded text, the search's byte offsets may count a byte order mark.
+      let line_starts: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+      let mut edits = Vec::new();
+      for m in json_file(result, state).matches {
+        let Some(&line_start) = m.line_number.checked_sub(1).and_then(|line| line_starts.get(line)) else {
+          return Err(changed());
+        };
+        if state.replace_text.kind == ReplaceTextKind::DeleteLine {
+          let end = line_start + m.text.len();
+          edits.push(Edit { start: line_start, end, original: m.text, replacement: String::new() });
+          continue;
+        }
+        for submatch in m.submatches {
+          match submatch.replacement {
+            Some(replacement) if replacement != submatch.text => {
+              edits.push(Edit {
+                start: line_start + submatch.start,
+                end: line_start + submatch.end,
+                original: submatch.text,
+                replacement,
+              });
+            },
+            _ => {},
+          }
+        }
+      }
+      edits.sort_by_key(|edit| edit.start);
+      edits.dedup_by_key(|edit| edit.start);
+
+      if edits.iter().any(|edit| text.get(edit.start..edit.end) != Some(edit.original.as_str())) {
+        return Err(changed());
+      }
+      if !edits.is_empty() {
+        let project_root = root_for_path(&result.path, &state.project_roots);
+        let root = state.project_roots.iter().position(|root| *root == project_root).unwrap_or(0);
+        files.push(FileChanges {
+          root,
+          path: relative_path(&result.path, &project_root).to_string(),
+          hash: file_hash(&bytes),
+          encoding: file_encoding.encoding.name().to_string(),
+          edits,
+        });
+      }
+    }
+
+    Ok(Self {
+      version: VERSION,
+      search_text: state.search_text.clone(),
+      replace_text: state.replace_text.clone(),
+      files,
+    })
+  }
+
+  pub fn edit_count(&self) -> usize {
+    self.files.iter().map(|file| file.edits.len()).sum()
+  }
+
+  /// File path matches of the listed results, which the change set leaves out.
+  pub fn left_out_renames(state: &State) -> usize {
+    state.search_result.list.iter().filter(|r| r.kind == SearchResultKind::FilePath && !r.removed).count()
+  }
+
+  pub fn save(&self, path: &Path) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn load(path: &Path) -> io::Result<Self> {
+    let change_set: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if change_set.version != VERSION {
+      let message = format!("change set version {} is not supported, expected {VERSION}", change_set.version);
+      return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    Ok(change_set)
+  }
+
+  /// Applies the edits of every file in one transaction, resolving each file against its root of `project_roots`.
+  /// When any file conflicts or can't be written, none is changed.
+  pub fn apply(&self, project_roots: &[PathBuf]) -> Vec<FileReplaceResult> {
+    let mut transaction = FileTransaction::new();
+    let mut results = Vec::new();
+    for file in &self.files {
+      let Some(project_root) = project_roots.get(file.root) else {
+        let reason =
+          format!("project root {} not given, pass the roots in the order they were searched", file.root + 1);
+        results.push(FileReplaceResult { path: file.path.clone(), outcome: ReplaceOutcome::Skipped(reason) });
+        continue;
+      };
+      let path = project_root.join(&file.path);
+      let outcome = match file.replaced_content(&path) {
+        Ok(content) => {
+          transaction.stage(&path, content);
+          ReplaceOutcome::Replaced
+        },
+        Err(outcome) => outcome,
+      };
+      results.push(FileReplaceResult { path: path.to_string_lossy().to_string(), outcome });
+    }
+
+    let conflicts = results.iter().any(|result| result.outcome != ReplaceOutcome::Replaced);
+    let failed = if conflicts { None } else { transaction.commit().err() };
+    if conflicts || failed.is_some() {
+      for result in results.iter_mut().filter(|result| result.outcome == ReplaceOutcome::Replaced) {
+        result.outcome = match &failed {
+          Some((failed_path, e)) if *failed_path == Path::new(&result.path) => ReplaceOutcome::Failed(e.to_string()),
+          _ => ReplaceOutcome::Skipped(NOT_APPLIED.to_string()),
+        };
+      }
+    }
+    results
+  }
+}
+
+impl FileChanges {
+  /// The file's new bytes, or why the edits no longer apply to it.
+  fn replaced_content(&self, path: &Path) -> Result<Vec<u8>, ReplaceOutcome> {
+    let bytes = fs::read(path).map_err(|e| ReplaceOutcome::Failed(e.to_string()))?;
+    if file_hash(&bytes) != self.hash {
+      return Err(ReplaceOutcome::Skipped("changed since the change set was exported".to_string()));
+    }
+    let configured = Encoding::for_label(self.encoding.as_bytes());
+    let (text, file_encoding) =
+      encoding::decode(&bytes, configured).map_err(|e| ReplaceOutcome::Failed(e.to_string()))?;
+
+    let mut new_text = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for edit in &self.edits {
+      if edit.start < last_end || text.get(edit.start..edit.end) != Some(edit.original.as_str()) {
+        return Err(ReplaceOutcome::Skipped(format!("edit at byte {} doesn't match the file", edit.start)));
+      }
+      new_text.push_str(&text[last_end..edit.start]);
+      new_text.push_str(&edit.replacement);
+      last_end = edit.end;
+    }
+    new_text.push_str(&text[last_end..]);
+    encoding::encode(&new_text, file_encoding).map_err(|e| ReplaceOutcome::Failed(e.to_string()))
+  }
+}
+
+/// FNV-1a, stable across builds and platforms unlike the standard library's hasher, so a change set exported on one
+/// machine can be checked on another.
+pub fn file_hash(bytes: &[u8]) -> String {
+  let hash =
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100_0000_01b3));
+  format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::{
+    redux::state::{Match, SearchListState, SearchResultState, SearchTextKind, SubMatch},
+    ripgrep::RipgrepLines,
+  };
+
+  #[test]
+  fn test_export_and_apply() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let roots = vec![dir.join("app"), dir.join("lib")];
+    roots.iter().for_each(|root| fs::create_dir_all(root).unwrap());
+    let first = roots[0].join("first.txt");
+    let second = roots[1].join("second.txt");
+    fs::write(&first, "let user_id = 1;\n").unwrap();
+    fs::write(&second, "user_id\n").unwrap();
+
+    let search_result = |path: &Path, text: &str, start: usize| {
+      SearchResultState {
+        path: path.to_string_lossy().to_string(),
+        matches: vec![Match {
+          line_number: 1,
+          lines: Some(RipgrepLines { text: text.to_string() }),
+          submatches: vec![SubMatch { start, end: start + 7, line_start: 0, line_end: 0 }],
+          ..Default::default()
+        }],
+        total_matches: 1,
+        ..Default::default()
+      }
+    };
+    let state = State {
+      search_text: SearchTextState { text: "user_id".to_string(), kind: SearchTextKind::MatchCase },
+      replace_text: ReplaceTextState { text: "account_id".to_string(), kind: ReplaceTextKind::Simple },
+      search_result: SearchListState {
+        list: vec![search_result(&first, "let user_id = 1;\n", 4), search_result(&second, "user_id\n", 0)],
+        ..Default::default()
+      },
+      ..State::new(roots.clone())
+    };
+
+    let change_set = ChangeSet::from_state(&state).unwrap();
+    assert_eq!(change_set.files[0].path, "first.txt");
+    assert_eq!((change_set.files[1].root, change_set.files[1].path.as_str()), (1, "second.txt"));
+    assert_eq!(change_set.files[0].edits, vec![Edit {
+      start: 4,
+      end: 11,
+      original: "user_id".to_string(),
+      replacement: "account_id".to_string()
+    }]);
+    let change_set_file = dir.join(CHANGE_SET_FILE);
+    change_set.save(&change_set_file).unwrap();
+    let change_set = ChangeSet::load(&change_set_file).unwrap();
+
+    let results = change_set.apply(&roots[..1]);
+    let missing_root = "project root 2 not given, pass the roots in the order they were searched".to_string();
+    assert_eq!(results[1].outcome, ReplaceOutcome::Skipped(missing_root));
+
+    fs::write(&second, "user_id = 2\n").unwrap();
+    let results = change_set.apply(&roots);
+    assert_eq!(results[0].outcome, ReplaceOutcome::Skipped(NOT_APPLIED.to_string()));
+    assert_eq!(results[1].outcome, ReplaceOutcome::Skipped("changed since the change set was exported".to_string()));
+    assert_eq!(fs::read_to_string(&first).unwrap(), "let user_id = 1;\n");
+
+    fs::write(&second, "user_id\n").unwrap();
+    let results = change_set.apply(&roots);
+    assert!(results.iter().all(|result| result.outcome == ReplaceOutcome::Replaced));
+    assert_eq!(fs::read_to_string(&first).unwrap(), "let account_id = 1;\n");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "account_id\n");
+  }
+
+  #[test]
+  fn test_export_with_bom() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let path = dir.join("bom.txt");
+    fs::write(&path, "\u{feff}one\nlet user_id = 1;\n").unwrap();
+
+    // The search counts the byte order mark in the match's offset.
+    let search_result = SearchResultState {
+      path: path.to_string_lossy().to_string(),
+      matches: vec![Match {
+        line_number: 2,
+        absolute_offset: 7,
+        lines: Some(RipgrepLines { text: "let user_id = 1;\n".to_string() }),
+        submatches: vec![SubMatch { start: 4, end: 11, line_start: 0, line_end: 0 }],
+        ..Default::default()
+      }],
+      total_matches: 1,
+      ..Default::default()
+    };
+    let state = State {
+      search_text: SearchTextState { text: "user_id".to_string(), kind: SearchTextKind::MatchCase },
+      replace_text: ReplaceTextState { text: "account_id".to_string(), kind: ReplaceTextKind::Simple },
+      search_result: SearchListState { list: vec![search_result], ..Default::default() },
+      ..State::new(vec![dir.clone()])
+    };
+
+    let change_set = ChangeSet::from_state(&state).unwrap();
+    assert_eq!((change_set.files[0].edits[0].start, change_set.files[0].edits[0].end), (8, 15));
+    assert!(change_set.apply(&[dir]).iter().all(|result| result.outcome == ReplaceOutcome::Replaced));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "\u{feff}one\nlet account_id = 1;\n");
+  }
+}
@@ -1,12 +1,268 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use crate::utils::version;
+use crate::{
+  app::QueryOptions,
+  redux::state::{AstGrepState, GitScope, GitState, ReplaceTextKind, SearchTextKind},
+  utils::version,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
 pub struct Cli {
-  #[arg(short, long, value_name = "PATH", help = "Path to the project root", default_value = ".")]
-  pub project_root: PathBuf,
+  #[arg(
+    short,
+    long,
+    value_name = "PATH",
+    help = "Path to the project root, repeat to search several roots",
+    default_value = "."
+  )]
+  pub project_root: Vec<PathBuf>,
+
+  #[arg(value_name = "FILES", help = "Only search these files or directories")]
+  pub files: Vec<PathBuf>,
+
+  #[arg(long, value_name = "FILE", help = "Only search the files listed in FILE, one per line ('-' reads stdin)")]
+  pub files_from: Option<PathBuf>,
+
+  #[arg(long, value_enum, default_value_t = ScopeArg::All, help = "Which files of the git repositories to search")]
+  pub scope: ScopeArg,
+
+  #[arg(long, value_name = "REF", help = "Only search files changed since REF in git, e.g. main")]
+  pub changed_since: Option<String>,
+
+  #[arg(long, help = "Commit the files changed by each replace, with the search and replace in the message")]
+  pub commit: bool,
+
+  #[arg(long, value_name = "BRANCH", help = "Create BRANCH for the replace commits, implies --commit")]
+  pub commit_branch: Option<String>,
+
+  #[arg(long, help = "Replace in all files or, when any of them can't be written, in none")]
+  pub transactional: bool,
+
+  #[arg(long, help = "Search files again when they change on disk, keeping the result list up to date")]
+  pub watch: bool,
+
+  #[arg(long, help = "Restore the query, removed results and selection of the last run in these project roots")]
+  pub resume: bool,
+
+  #[arg(short, long, value_name = "TEXT", help = "Start with this search text")]
+  pub search: Option<String>,
+
+  #[arg(long, value_enum, value_name = "MODE", help = "How the search text matches, simple when omitted")]
+  pub search_mode: Option<SearchModeArg>,
+
+  #[arg(short, long, value_name = "TEXT", help = "Start with this replace text")]
+  pub replace: Option<String>,
+
+  #[arg(long, value_enum, value_name = "MODE", help = "How the replace text is applied, simple when omitted")]
+  pub replace_mode: Option<ReplaceModeArg>,
+
+  #[arg(long, help = "Print the search results as JSON Lines instead of starting the interface")]
+  pub json: bool,
+
+  #[arg(
+    long,
+    value_name = "FILE",
+    help = "Write the matches as a Vim quickfix list to FILE ('-' for stdout) instead of starting the interface"
+  )]
+  pub quickfix: Option<PathBuf>,
+
+  #[arg(long, help = "Open $EDITOR with -q on the quickfix list of the matches instead of starting the interface")]
+  pub edit: bool,
+
+  #[arg(
+    long,
+    value_name = "SOCKET",
+    help = "Accept JSON-RPC requests from an editor on the Unix socket SOCKET while the interface runs"
+  )]
+  pub listen: Option<PathBuf>,
+
+  #[arg(
+    long,
+    value_name = "FILE",
+    help = "Apply a change set exported with Ctrl-x without starting the interface, when no file changed since"
+  )]
+  pub apply_changes: Option<PathBuf>,
+
+  #[arg(
+    short = 'E',
+    long,
+    value_name = "ENCODING",
+    help = "Encoding of files without a byte order mark, e.g. latin1 or shift_jis ('auto' for UTF-8)"
+  )]
+  pub encoding: Option<String>,
+
+  #[cfg(feature = "ast_grep")]
+  #[arg(long, value_name = "LANG", help = "Language of the AST grep pattern, inferred per file when omitted")]
+  pub lang: Option<String>,
+
+  #[cfg(feature = "ast_grep")]
+  #[arg(
+    long,
+    value_name = "FILE",
+    num_args = 0..=1,
+    help = "Search with an ast-grep rule file, or the project's sgconfig.yml when no FILE is given"
+  )]
+  pub rule: Option<Option<PathBuf>>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeArg {
+  /// Every file that is not ignored
+  All,
+  /// Files tracked by git, including committed files that are ignored locally
+  Tracked,
+  /// Files with changes in the working tree compared to HEAD
+  Modified,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchModeArg {
+  Simple,
+  MatchCase,
+  MatchWholeWord,
+  MatchCaseWholeWord,
+  Regex,
+  /// The search text is an AST grep pattern
+  #[cfg(feature = "ast_grep")]
+  AstGrep,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplaceModeArg {
+  Simple,
+  /// Matches the case of each match: lower, upper or capitalized
+  PreserveCase,
+  /// Matches the case style of each match, e.g. camelCase or snake_case
+  SmartCase,
+  /// Deletes the matched lines, ignoring the replace text
+  DeleteLine,
+  /// The replace text is an AST grep rewrite
+  #[cfg(feature = "ast_grep")]
+  AstGrep,
+}
+
+impl From<SearchModeArg> for SearchTextKind {
+  fn from(mode: SearchModeArg) -> Self {
+    match mode {
+      SearchModeArg::Simple => SearchTextKind::Simple,
+      SearchModeArg::MatchCase => SearchTextKind::MatchCase,
+      SearchModeArg::MatchWholeWord => SearchTextKind::MatchWholeWord,
+      SearchModeArg::MatchCaseWholeWord => SearchTextKind::MatchCaseWholeWord,
+      SearchModeArg::Regex => SearchTextKind::Regex,
+      #[cfg(feature = "ast_grep")]
+      SearchModeArg::AstGrep => SearchTextKind::AstGrep,
+    }
+  }
+}
+
+impl From<ReplaceModeArg> for ReplaceTextKind {
+  fn from(mode: ReplaceModeArg) -> Self {
+    match mode {
+      ReplaceModeArg::Simple => ReplaceTextKind::Simple,
+      ReplaceModeArg::PreserveCase => ReplaceTextKind::PreserveCase,
+      ReplaceModeArg::SmartCase => ReplaceTextKind::SmartCase,
+      ReplaceModeArg::DeleteLine => ReplaceTextKind::DeleteLine,
+      #[cfg(feature = "ast_grep")]
+      ReplaceModeArg::AstGrep => ReplaceTextKind::AstGrep,
+    }
+  }
+}
+
+impl Cli {
+  /// Files the search is restricted to, combining the positional paths with the `--files-from` list.
+  pub fn search_files(&self) -> std::io::Result<Option<Vec<PathBuf>>> {
+    let mut files = self.files.clone();
+
+    if let Some(files_from) = &self.files_from {
+      let content = if files_from.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+      } else {
+        std::fs::read_to_string(files_from)?
+      };
+      files.extend(content.lines().map(str::trim).filter(|line| !line.is_empty()).map(PathBuf::from));
+    }
+
+    Ok(if files.is_empty() && self.files_from.is_none() { None } else { Some(files) })
+  }
+
+  /// Git options given on the command line, `--changed-since` taking precedence over `--scope`.
+  pub fn git_state(&self) -> GitState {
+    let scope = match (&self.changed_since, self.scope) {
+      (Some(base_ref), _) => GitScope::ChangedSince(base_ref.clone()),
+      (None, ScopeArg::All) => GitScope::All,
+      (None, ScopeArg::Tracked) => GitScope::Tracked,
+      (None, ScopeArg::Modified) => GitScope::Modified,
+    };
+    GitState {
+      scope,
+      base_ref: self.changed_since.clone(),
+      auto_commit: self.commit || self.commit_branch.is_some(),
+      commit_branch: self.commit_branch.clone(),
+    }
+  }
+
+  /// Search and replace given on the command line, an AST grep search also rewriting in AST grep mode.
+  pub fn query_options(&self) -> QueryOptions {
+    let search_kind = self.search_mode.map(SearchTextKind::from);
+    let replace_kind = self.replace_mode.map(ReplaceTextKind::from);
+    QueryOptions { search_text: self.search.clone(), search_kind, replace_text: self.replace.clone(), replace_kind }
+  }
+
+  /// AST grep options given on the command line, resolving a bare `--rule` to the first project root's sgconfig.yml.
+  #[cfg(feature = "ast_grep")]
+  pub fn ast_grep_state(&self) -> std::io::Result<AstGrepState> {
+    let rule_file = match &self.rule {
+      Some(Some(rule_file)) => Some(rule_file.clone()),
+      Some(None) => Some(self.project_root.first().cloned().unwrap_or_default().join("sgconfig.yml")),
+      None => None,
+    };
+
+    if let Some(rule_file) = &rule_file {
+      if !rule_file.is_file() {
+        return Err(std::io::Error::new(
+          std::io::ErrorKind::NotFound,
+          format!("Rule file {} not found", rule_file.display()),
+        ));
+      }
+    }
+
+    Ok(AstGrepState { lang: self.lang.clone(), rule_file })
+  }
+
+  #[cfg(not(feature = "ast_grep"))]
+  pub fn ast_grep_state(&self) -> std::io::Result<AstGrepState> {
+    Ok(AstGrepState::default())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_search_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let files_from = temp_dir.path().join("files.txt");
+    let files_from_arg = files_from.to_string_lossy().to_string();
+    let search_files =
+      |args: &[&str]| Cli::parse_from(std::iter::once("serpl").chain(args.iter().copied())).search_files().unwrap();
+
+    assert_eq!(search_files(&[]), None);
+    assert_eq!(search_files(&["src/main.rs"]), Some(vec![PathBuf::from("src/main.rs")]));
+
+    std::fs::write(&files_from, "  src/app.rs \n\n\tsrc/cli.rs\n   \n").unwrap();
+    assert_eq!(
+      search_files(&["src/main.rs", "--files-from", &files_from_arg]),
+      Some(vec![PathBuf::from("src/main.rs"), PathBuf::from("src/app.rs"), PathBuf::from("src/cli.rs")])
+    );
+
+    // An empty list still restricts the search, to no files at all.
+    std::fs::write(&files_from, "\n").unwrap();
+    assert_eq!(search_files(&["--files-from", &files_from_arg]), Some(vec![]));
+  }
 }
@@ -11,12 +11,14 @@ use crate::{
   tui::{Event, Frame},
 };
 
+pub mod confirm_dirty_files_dialog;
 pub mod confirm_empty_replace_dialog;
 pub mod confirm_git_dir_dialog;
 pub mod help_dialog;
 pub mod notifications;
 pub mod preview;
 pub mod replace;
+pub mod replace_summary_dialog;
 pub mod search;
 pub mod search_result;
 pub mod small_help;
@@ -0,0 +1,116 @@
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, Frame};
+use crate::{
+  action::AppAction,
+  redux::{
+    action::Action,
+    state::{Dialog, DirtyFilesChoice, DirtyFilesDialogState, State},
+    thunk::ThunkAction,
+  },
+  ui::choice_dialog_widget::{ChoiceDialogState, ChoiceDialogWidget},
+};
+
+/// Files listed in the dialog before the rest are summarized.
+const MAX_LISTED_FILES: usize = 8;
+
+const CHOICES: [&str; 4] = ["Proceed", "Skip Files", "Stash First", "Cancel"];
+
+#[derive(Default)]
+pub struct ConfirmDirtyFilesDialog {
+  command_tx: Option<UnboundedSender<AppAction>>,
+  dialog_state: ChoiceDialogState,
+}
+
+impl ConfirmDirtyFilesDialog {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn handle_choice(&mut self, dialog: &DirtyFilesDialogState) {
+    let command_tx = self.command_tx.as_ref().unwrap();
+    let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
+    command_tx.send(hide_dialog).unwrap();
+
+    let choice = match CHOICES[self.dialog_state.selected] {
+      "Proceed" => DirtyFilesChoice::Proceed,
+      "Skip Files" => {
+        let skip_files = AppAction::Action(Action::RemoveFilesFromList { paths: dialog.files.clone() });
+        command_tx.send(skip_files).unwrap();
+        // A single file or line replace only asks about its own file, so nothing is left to replace.
+        if !matches!(dialog.replace, ThunkAction::ProcessReplace(_)) {
+          self.dialog_state = ChoiceDialogState::default();
+          return;
+        }
+        DirtyFilesChoice::Proceed
+      },
+      "Stash First" => DirtyFilesChoice::Stash,
+      _ => {
+        self.dialog_state = ChoiceDialogState::default();
+        return;
+      },
+    };
+    self.dialog_state = ChoiceDialogState::default();
+
+    let choice_action = AppAction::Action(Action::SetDirtyFilesChoice { choice: Some(choice) });
+    command_tx.send(choice_action).unwrap();
+    command_tx.send(AppAction::Thunk(dialog.replace.clone())).unwrap();
+  }
+
+  fn message(dialog: &DirtyFilesDialogState) -> String {
+    let mut message = "These files have uncommitted changes that the replacement would mix with:\n".to_string();
+    for file in dialog.files.iter().take(MAX_LISTED_FILES) {
+      message.push_str(&format!("{file}\n"));
+    }
+    if dialog.files.len() > MAX_LISTED_FILES {
+      message.push_str(&format!("... and {} more\n", dialog.files.len() - MAX_LISTED_FILES));
+    }
+    message
+  }
+}
+
+impl Component for ConfirmDirtyFilesDialog {
+  fn register_action_handler(&mut self, tx: UnboundedSender<AppAction>) -> Result<()> {
+    self.command_tx = Some(tx);
+    Ok(())
+  }
+
+  fn handle_key_events(&mut self, key: KeyEvent, state: &State) -> Result<Option<AppAction>> {
+    if let Some(Dialog::ConfirmDirtyFiles(dialog)) = &state.dialog {
+      match key.code {
+        KeyCode::Tab | KeyCode::Right | KeyCode::Down | KeyCode::Char('j') | KeyCode::Char('l') => {
+          self.dialog_state.next(CHOICES.len());
+        },
+        KeyCode::BackTab | KeyCode::Left | KeyCode::Up | KeyCode::Char('k') | KeyCode::Char('h') => {
+          self.dialog_state.previous(CHOICES.len());
+        },
+        KeyCode::Enter => self.handle_choice(dialog),
+        KeyCode::Esc | KeyCode::Char('n') => {
+          self.dialog_state = ChoiceDialogState::default();
+          let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
+          self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+        },
+        _ => {},
+      }
+    }
+    Ok(None)
+  }
+
+  fn draw(&mut self, f: &mut Frame<'_>, rect: Rect, state: &State) -> Result<()> {
+    if let Some(Dialog::ConfirmDirtyFiles(dialog)) = &state.dialog {
+      let dialog_widget = ChoiceDialogWidget::new(
+        "Uncommitted Changes".to_string(),
+        Self::message(dialog),
+        CHOICES.iter().map(|choice| choice.to_string()).collect(),
+      );
+
+      if dialog.show {
+        f.render_stateful_widget(dialog_widget, rect, &mut self.dialog_state);
+      }
+    }
+    Ok(())
+  }
+}
@@ -45,13 +45,14 @@ impl ConfirmEmptyReplaceDialog {
   fn handle_input(&self, action: DialogAction, state: &State) {
     match action {
       DialogAction::ConfirmReplace => {
-        let process_replace_action = AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(true)));
-        self.command_tx.as_ref().unwrap().send(process_replace_action).unwrap();
-
+        // Hidden first, the replace may go on with the confirmation of a root.
         let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
         self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+
+        let process_replace_action = AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(true)));
+        self.command_tx.as_ref().unwrap().send(process_replace_action).unwrap();
       },
-      DialogAction::CancelReplace => {
+      DialogAction::CancelReplace | DialogAction::ConfirmRoot(_) | DialogAction::SkipRoot(_) => {
         let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
         self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
       },
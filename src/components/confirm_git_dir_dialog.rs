@@ -44,19 +44,33 @@ impl ConfirmGitDirDialog {
 
   fn handle_input(&self, action: DialogAction, state: &State) {
     match action {
-      DialogAction::ConfirmReplace => {
-        let process_replace_action = AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(true)));
-        self.command_tx.as_ref().unwrap().send(process_replace_action).unwrap();
-
-        let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
-        self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
-      },
+      DialogAction::ConfirmReplace => self.continue_replace(),
       DialogAction::CancelReplace => {
         let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
         self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
       },
+      DialogAction::ConfirmRoot(root) => {
+        let confirm_root = AppAction::Action(Action::ConfirmProjectRoot { root });
+        self.command_tx.as_ref().unwrap().send(confirm_root).unwrap();
+        self.continue_replace();
+      },
+      DialogAction::SkipRoot(root) => {
+        let skip_root = AppAction::Action(Action::RemoveProjectRootFromList { root });
+        self.command_tx.as_ref().unwrap().send(skip_root).unwrap();
+        self.continue_replace();
+      },
     }
   }
+
+  /// Hides the dialog first, re-running the replace may open it again for the next root. The empty replacement
+  /// prompt comes before the roots, so it was already answered.
+  fn continue_replace(&self) {
+    let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
+    self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+
+    let process_replace_action = AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(true)));
+    self.command_tx.as_ref().unwrap().send(process_replace_action).unwrap();
+  }
 }
 
 impl Component for ConfirmGitDirDialog {
@@ -88,8 +102,13 @@ impl Component for ConfirmGitDirDialog {
                 self.handle_input(action.clone(), state);
               },
               ConfirmDialogAction::Cancel => {
-                let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
-                self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+                match &dialog.on_cancel {
+                  Some(cancel_action) => self.handle_input(cancel_action.clone(), state),
+                  None => {
+                    let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
+                    self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+                  },
+                }
               },
             }
             Ok(None)
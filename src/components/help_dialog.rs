@@ -47,7 +47,7 @@ impl HelpDialog {
   // }
 
   fn global_keybindings() -> String {
-    "- Ctrl-c: Quit\n- Ctrl-d: Quit\n- Ctrl-b: Help dialog\n- Ctrl-o: Process Replace For All Files\n- Ctrl-n: Loop through search and replace modes\n- Enter: Select/Deselect file\n- d: delete file/delete line from the replace process\n- r: Replace Selected File Or Line".to_string()
+    "- Ctrl-c: Quit\n- Ctrl-d: Quit\n- Ctrl-b: Help dialog\n- Ctrl-o: Process Replace For All Files\n- Ctrl-x: Export the replacements as a change set to serpl-changes.json\n- Ctrl-q: Export the matches as a Vim quickfix list to serpl-quickfix.txt\n- Ctrl-n: Loop through search and replace modes\n- Ctrl-p: Include file paths in the search, renaming matching files on replace\n- Ctrl-l: Cycle the AST Grep pattern language\n- Ctrl-g: Cycle the git scope: all, tracked, modified or changed files\n- Enter: Select/Deselect file\n- d: delete file/delete line from the replace process\n- r: Replace Selected File Or Line".to_string()
   }
 
   fn navigation_keybindings() -> String {
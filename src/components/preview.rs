@@ -19,9 +19,12 @@ use crate::{
   layout::get_layout,
   redux::{
     action::Action,
-    state::{FocusedScreen, ReplaceTextKind, SearchResultState, SearchTextKind, State, SubMatch},
+    state::{
+      FocusedScreen, MetaVariableBinding, ReplaceTextKind, ReplaceTextState, SearchResultKind, SearchResultState,
+      SearchTextKind, State, SubMatch,
+    },
     thunk::ThunkAction,
-    utils::{apply_replace, get_search_regex},
+    utils::{apply_replace_captures, get_renamed_path, try_get_search_regex},
   },
   tabs::Tab,
 };
@@ -33,6 +36,8 @@ pub struct Preview {
   lines_state: ListState,
   total_lines: usize,
   non_divider_lines: Vec<usize>,
+  /// Index of the match each non divider line belongs to, a multi-line AST grep match spans several lines.
+  non_divider_matches: Vec<usize>,
 }
 
 impl Preview {
@@ -47,6 +52,7 @@ impl Preview {
       },
       total_lines: 0,
       non_divider_lines: vec![],
+      non_divider_matches: vec![],
     }
   }
 
@@ -91,9 +97,18 @@ impl Preview {
     }
   }
 
+  fn selected_match_index(&self, selected_index: usize) -> usize {
+    self
+      .non_divider_lines
+      .iter()
+      .position(|&index| index == selected_index)
+      .and_then(|pos| self.non_divider_matches.get(pos).copied())
+      .unwrap_or(0)
+  }
+
   fn delete_line(&mut self, selected_result_state: &SearchResultState) {
     if let Some(selected_index) = self.lines_state.selected() {
-      let line_index = self.non_divider_lines.iter().position(|&index| index == selected_index).unwrap_or(0);
+      let line_index = self.selected_match_index(selected_index);
       let file_index = selected_result_state.index.unwrap_or(0);
       let remove_line_from_file_thunk = AppAction::Thunk(ThunkAction::RemoveLineFromFile(file_index, line_index));
       self.command_tx.as_ref().unwrap().send(remove_line_from_file_thunk).unwrap();
@@ -102,7 +117,7 @@ impl Preview {
 
   fn replace_selected_line(&mut self, selected_result_state: &SearchResultState) {
     if let Some(selected_index) = self.lines_state.selected() {
-      let line_index = self.non_divider_lines.iter().position(|&index| index == selected_index).unwrap_or(0);
+      let line_index = self.selected_match_index(selected_index);
       let file_index = selected_result_state.index.unwrap_or(0);
       let replace_line_thunk = AppAction::Thunk(ThunkAction::ProcessLineReplace(file_index, line_index));
       self.command_tx.as_ref().unwrap().send(replace_line_thunk).unwrap();
@@ -115,12 +130,14 @@ impl Preview {
     &self,
     full_match: &'a str,
     submatches: &[SubMatch],
-    replace_text: &'a str,
+    search_text: &str,
+    replace_text_state: &'a ReplaceTextState,
     replacement: &'a Option<String>,
     search_kind: &SearchTextKind,
-    replace_kind: &ReplaceTextKind,
     is_ast_grep: bool,
   ) -> Vec<Line<'a>> {
+    let replace_text = replace_text_state.text.as_str();
+    let replace_kind = &replace_text_state.kind;
     let mut lines = Vec::new();
     let match_lines: Vec<&str> = full_match.lines().collect();
     let replacement_lines: Vec<&str> = replacement.as_ref().map(|r| r.lines().collect()).unwrap_or_default();
@@ -169,10 +186,15 @@ impl Preview {
               spans.push(Span::raw(common_suffix));
             }
           } else {
-            let re = get_search_regex(matched_text, search_kind);
+            // Regex mode needs the real pattern so capture groups can be expanded in the preview.
+            let re = if *search_kind == SearchTextKind::Regex {
+              try_get_search_regex(search_text, search_kind).ok()
+            } else {
+              try_get_search_regex(matched_text, search_kind).ok()
+            };
             let mut last_match_end = 0;
 
-            for cap in re.captures_iter(matched_text) {
+            for cap in re.iter().flat_map(|re| re.captures_iter(matched_text)) {
               let m = cap.get(0).unwrap();
               let match_start = m.start();
               let match_end = m.end();
@@ -184,7 +206,7 @@ impl Preview {
               if replace_text.is_empty() {
                 spans.push(Span::styled(&matched_text[match_start..match_end], Style::default().bg(Color::Blue)));
               } else {
-                let replacement = apply_replace(&matched_text[match_start..match_end], replace_text, replace_kind);
+                let replacement = apply_replace_captures(&cap, search_kind, replace_text_state);
                 spans.push(Span::styled(
                   &matched_text[match_start..match_end],
                   Style::default().fg(Color::White).bg(Color::LightRed).add_modifier(Modifier::CROSSED_OUT),
@@ -214,6 +236,59 @@ impl Preview {
     lines
   }
 
+  fn format_path_rename<'a>(&self, old_path: &'a str, submatches: &[SubMatch], state: &State) -> Line<'a> {
+    let replace_text = &state.replace_text.text;
+    let matched_style = if replace_text.is_empty() {
+      Style::default().bg(Color::Blue)
+    } else {
+      Style::default().fg(Color::White).bg(Color::LightRed).add_modifier(Modifier::CROSSED_OUT)
+    };
+
+    let mut spans = Vec::new();
+    let mut last_end = 0;
+    for submatch in submatches {
+      if submatch.start > last_end {
+        spans.push(Span::raw(&old_path[last_end..submatch.start]));
+      }
+      spans.push(Span::styled(&old_path[submatch.start..submatch.end], matched_style));
+      last_end = submatch.end;
+    }
+    if last_end < old_path.len() {
+      spans.push(Span::raw(&old_path[last_end..]));
+    }
+
+    if !replace_text.is_empty() {
+      let new_path = get_renamed_path(old_path, &state.search_text, &state.replace_text);
+      spans.push(Span::raw(" → "));
+      spans.push(Span::styled(new_path, Style::default().fg(Color::White).bg(Color::Green)));
+    }
+
+    Line::from(spans)
+  }
+
+  /// Panel listing the meta variables captured by the selected AST grep match.
+  fn meta_variables_widget(bindings: &[MetaVariableBinding]) -> List<'_> {
+    let items: Vec<Line> = bindings
+      .iter()
+      .map(|binding| {
+        let range = match binding.range {
+          Some(((start_line, start_column), (end_line, end_column))) => {
+            format!(" {}:{}-{}:{}", start_line + 1, start_column + 1, end_line + 1, end_column + 1)
+          },
+          None => " transformed".to_string(),
+        };
+        Line::from(vec![
+          Span::styled(format!("{} ", binding.name), Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)),
+          Span::styled(binding.text.replace('\n', "↵"), Style::default().fg(Color::White)),
+          Span::styled(range, Style::default().fg(Color::DarkGray)),
+        ])
+      })
+      .collect();
+
+    List::new(items)
+      .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Meta Variables"))
+  }
+
   fn find_common_parts<'a>(s1: &'a str, s2: &'a str) -> (&'a str, &'a str) {
     let mut prefix_len = 0;
     for (c1, c2) in s1.chars().zip(s2.chars()) {
@@ -309,11 +384,22 @@ impl Component for Preview {
     };
 
     let mut lines = vec![];
+    let mut match_start_lines = vec![];
     self.non_divider_lines.clear();
+    self.non_divider_matches.clear();
 
     for (match_index, result) in state.selected_result.matches.iter().enumerate() {
+      if state.selected_result.kind == SearchResultKind::FilePath {
+        let old_path = result.lines.as_ref().map_or("", |lines| lines.text.as_str());
+        self.non_divider_lines.push(lines.len());
+        self.non_divider_matches.push(match_index);
+        lines.push(self.format_path_rename(old_path, &result.submatches, state));
+        continue;
+      }
+
       let line_number = result.line_number;
       let start_index = lines.len();
+      match_start_lines.push(start_index);
       let is_selected = self.lines_state.selected().map(|s| s >= start_index).unwrap_or(false);
 
       for (i, line) in result.context_before.iter().enumerate() {
@@ -334,16 +420,17 @@ impl Component for Preview {
       let formatted_lines = self.format_match_lines(
         &result.lines.as_ref().unwrap().text,
         &result.submatches,
-        &state.replace_text.text,
+        &state.search_text.text,
+        &state.replace_text,
         &result.replacement,
         &state.search_text.kind,
-        &state.replace_text.kind,
         is_ast_grep,
       );
       for (i, formatted_line) in formatted_lines.clone().into_iter().enumerate() {
         let mut spans = vec![Span::styled(format!("{:4} ", line_number + i), Style::default().fg(Color::LightGreen))];
         spans.extend(formatted_line.spans);
         self.non_divider_lines.push(lines.len());
+        self.non_divider_matches.push(match_index);
         lines.push(Line::from(spans));
       }
 
@@ -368,7 +455,22 @@ impl Component for Preview {
     let preview_widget =
       List::new(text).highlight_style(highlight_style).block(block).highlight_symbol("> ").scroll_padding(4);
 
-    f.render_stateful_widget(preview_widget, layout.preview, &mut self.lines_state);
+    let selected_match = self
+      .lines_state
+      .selected()
+      .and_then(|selected| match_start_lines.iter().rposition(|&start| start <= selected))
+      .and_then(|match_index| state.selected_result.matches.get(match_index));
+    let meta_variables = selected_match.map(|m| m.meta_variables.as_slice()).unwrap_or_default();
+
+    if meta_variables.is_empty() {
+      f.render_stateful_widget(preview_widget, layout.preview, &mut self.lines_state);
+    } else {
+      let panel_height = (meta_variables.len() as u16 + 2).min(layout.preview.height / 2);
+      let [preview_area, meta_variables_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(panel_height)]).areas(layout.preview);
+      f.render_stateful_widget(preview_widget, preview_area, &mut self.lines_state);
+      f.render_widget(Self::meta_variables_widget(meta_variables), meta_variables_area);
+    }
 
     Ok(())
   }
@@ -34,6 +34,11 @@ impl Replace {
     Self::default()
   }
 
+  /// Starts with `text` in the input, like a resumed session's query.
+  pub fn with_text(text: &str) -> Self {
+    Self { input: Input::new(text.to_string()), ..Self::default() }
+  }
+
   fn handle_input(&mut self, key: KeyEvent, state: &State) {
     self.input.handle_event(&crossterm::event::Event::Key(key));
     let query = self.input.value();
@@ -78,7 +83,8 @@ impl Component for Replace {
         (KeyCode::Char('n'), KeyModifiers::CONTROL) => {
           let replace_text_kind = match state.replace_text.kind {
             ReplaceTextKind::Simple => ReplaceTextKind::PreserveCase,
-            ReplaceTextKind::PreserveCase => ReplaceTextKind::DeleteLine,
+            ReplaceTextKind::PreserveCase => ReplaceTextKind::SmartCase,
+            ReplaceTextKind::SmartCase => ReplaceTextKind::DeleteLine,
             ReplaceTextKind::DeleteLine => {
               #[cfg(feature = "ast_grep")]
               {
@@ -113,8 +119,10 @@ impl Component for Replace {
   }
 
   fn update(&mut self, action: AppAction) -> Result<Option<AppAction>> {
-    if let AppAction::Tui(TuiAction::Reset) = action {
-      self.input.reset()
+    match action {
+      AppAction::Tui(TuiAction::Reset) => self.input.reset(),
+      AppAction::Tui(TuiAction::SetReplaceInput(text)) => self.input = Input::new(text),
+      _ => {},
     }
     Ok(None)
   }
@@ -125,6 +133,7 @@ impl Component for Replace {
     let replace_kind = match state.replace_text.kind {
       ReplaceTextKind::Simple => "[Simple]",
       ReplaceTextKind::PreserveCase => "[Preserve Case]",
+      ReplaceTextKind::SmartCase => "[Smart Case]",
       ReplaceTextKind::DeleteLine => "[Delete Line]",
       #[cfg(feature = "ast_grep")]
       ReplaceTextKind::AstGrep => "[AST Grep]",
@@ -146,11 +155,13 @@ impl Component for Replace {
 
     let replace_text = if state.replace_text.kind == ReplaceTextKind::DeleteLine {
       "[Entire line will be deleted]"
+    } else if state.uses_ast_grep_rule() {
+      "[Replaced by the rule's fix]"
     } else {
       self.input.value()
     };
 
-    let replace_style = if state.replace_text.kind == ReplaceTextKind::DeleteLine {
+    let replace_style = if state.replace_text.kind == ReplaceTextKind::DeleteLine || state.uses_ast_grep_rule() {
       Style::default().fg(Color::DarkGray)
     } else {
       Style::default().fg(Color::White)
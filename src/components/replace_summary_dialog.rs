@@ -0,0 +1,85 @@
+use color_eyre::eyre::Result;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::prelude::*;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{Component, Frame};
+use crate::{
+  action::AppAction,
+  redux::{
+    action::Action,
+    state::{Dialog, ReplaceOutcome, ReplaceSummaryDialogState, State},
+  },
+  ui::choice_dialog_widget::{ChoiceDialogState, ChoiceDialogWidget},
+};
+
+/// Skipped and failed files listed in the dialog before the rest are summarized, all of them are in the log.
+const MAX_LISTED_FILES: usize = 10;
+
+#[derive(Default)]
+pub struct ReplaceSummaryDialog {
+  command_tx: Option<UnboundedSender<AppAction>>,
+  dialog_state: ChoiceDialogState,
+}
+
+impl ReplaceSummaryDialog {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  fn message(dialog: &ReplaceSummaryDialogState) -> String {
+    let count = |outcome: fn(&ReplaceOutcome) -> bool| dialog.results.iter().filter(|r| outcome(&r.outcome)).count();
+    let replaced = count(|outcome| *outcome == ReplaceOutcome::Replaced);
+    let skipped = count(|outcome| matches!(outcome, ReplaceOutcome::Skipped(_)));
+    let failed = count(|outcome| matches!(outcome, ReplaceOutcome::Failed(_)));
+
+    let mut message = format!("Replaced {replaced} files, skipped {skipped}, failed {failed}\n\n");
+    let unreplaced: Vec<String> = dialog
+      .results
+      .iter()
+      .filter_map(|result| {
+        match &result.outcome {
+          ReplaceOutcome::Replaced => None,
+          ReplaceOutcome::Skipped(reason) => Some(format!("Skipped {}: {reason}", result.path)),
+          ReplaceOutcome::Failed(reason) => Some(format!("Failed {}: {reason}", result.path)),
+        }
+      })
+      .collect();
+    for line in unreplaced.iter().take(MAX_LISTED_FILES) {
+      message.push_str(&format!("{line}\n"));
+    }
+    if unreplaced.len() > MAX_LISTED_FILES {
+      message.push_str(&format!("... and {} more, see the log\n", unreplaced.len() - MAX_LISTED_FILES));
+    }
+    message
+  }
+}
+
+impl Component for ReplaceSummaryDialog {
+  fn register_action_handler(&mut self, tx: UnboundedSender<AppAction>) -> Result<()> {
+    self.command_tx = Some(tx);
+    Ok(())
+  }
+
+  fn handle_key_events(&mut self, key: KeyEvent, state: &State) -> Result<Option<AppAction>> {
+    if let Some(Dialog::ReplaceSummary(_)) = &state.dialog {
+      if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+        let hide_dialog = AppAction::Action(Action::SetDialog { dialog: None });
+        self.command_tx.as_ref().unwrap().send(hide_dialog).unwrap();
+      }
+    }
+    Ok(None)
+  }
+
+  fn draw(&mut self, f: &mut Frame<'_>, rect: Rect, state: &State) -> Result<()> {
+    if let Some(Dialog::ReplaceSummary(dialog)) = &state.dialog {
+      let dialog_widget =
+        ChoiceDialogWidget::new("Replace Summary".to_string(), Self::message(dialog), vec!["Close".to_string()]);
+
+      if dialog.show {
+        f.render_stateful_widget(dialog_widget, rect, &mut self.dialog_state);
+      }
+    }
+    Ok(())
+  }
+}
@@ -15,6 +15,7 @@ use tui_input::{backend::crossterm::EventHandler, Input};
 use super::{Component, Frame};
 use crate::{
   action::{AppAction, TuiAction},
+  astgrep::next_language,
   components::notifications::NotificationEnum,
   config::{Config, KeyBindings},
   layout::get_layout,
@@ -42,6 +43,11 @@ impl Search {
     Self::default()
   }
 
+  /// Starts with `text` in the input, like a resumed session's query.
+  pub fn with_text(text: &str) -> Self {
+    Self { input: Input::new(text.to_string()), ..Self::default() }
+  }
+
   fn set_selected_result(&mut self, state: &State) {
     let first_result = match state.search_result.list.first() {
       Some(result) => result.clone(),
@@ -90,6 +96,35 @@ impl Search {
     self.command_tx.as_ref().unwrap().send(process_search_thunk).unwrap();
     self.set_selected_result(state);
   }
+
+  #[cfg(feature = "ast_grep")]
+  fn cycle_ast_grep_language(&mut self, state: &State) {
+    let lang = next_language(state.ast_grep.lang.as_deref());
+    let lang_action = AppAction::Action(Action::SetAstGrepLanguage { lang });
+    self.command_tx.as_ref().unwrap().send(lang_action).unwrap();
+
+    let process_search_thunk = AppAction::Thunk(ThunkAction::ProcessSearch);
+    self.command_tx.as_ref().unwrap().send(process_search_thunk).unwrap();
+    self.set_selected_result(state);
+  }
+
+  fn cycle_git_scope(&mut self, state: &State) {
+    let scope_action = AppAction::Action(Action::SetGitScope { scope: state.git.next_scope() });
+    self.command_tx.as_ref().unwrap().send(scope_action).unwrap();
+
+    let process_search_thunk = AppAction::Thunk(ThunkAction::ProcessSearch);
+    self.command_tx.as_ref().unwrap().send(process_search_thunk).unwrap();
+    self.set_selected_result(state);
+  }
+
+  fn toggle_file_paths(&mut self, state: &State) {
+    let include_action = AppAction::Action(Action::SetIncludeFilePaths { include: !state.include_file_paths });
+    self.command_tx.as_ref().unwrap().send(include_action).unwrap();
+
+    let process_search_thunk = AppAction::Thunk(ThunkAction::ProcessSearch);
+    self.command_tx.as_ref().unwrap().send(process_search_thunk).unwrap();
+    self.set_selected_result(state);
+  }
 }
 
 impl Component for Search {
@@ -123,6 +158,19 @@ impl Component for Search {
           self.change_kind(search_text_kind, state);
           Ok(None)
         },
+        (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+          self.toggle_file_paths(state);
+          Ok(None)
+        },
+        (KeyCode::Char('g'), KeyModifiers::CONTROL) => {
+          self.cycle_git_scope(state);
+          Ok(None)
+        },
+        #[cfg(feature = "ast_grep")]
+        (KeyCode::Char('l'), KeyModifiers::CONTROL) if state.search_text.kind == SearchTextKind::AstGrep => {
+          self.cycle_ast_grep_language(state);
+          Ok(None)
+        },
         (KeyCode::Enter, _) => {
           self.handle_input(key, state);
           Ok(None)
@@ -161,8 +209,10 @@ impl Component for Search {
   }
 
   fn update(&mut self, action: AppAction) -> Result<Option<AppAction>> {
-    if let AppAction::Tui(TuiAction::Reset) = action {
-      self.input.reset()
+    match action {
+      AppAction::Tui(TuiAction::Reset) => self.input.reset(),
+      AppAction::Tui(TuiAction::SetSearchInput(text)) => self.input = Input::new(text),
+      _ => {},
     }
     Ok(None)
   }
@@ -171,13 +221,25 @@ impl Component for Search {
     let layout = get_layout(area);
 
     let search_kind = match state.search_text.kind {
-      SearchTextKind::Simple => "[Simple]",
-      SearchTextKind::MatchCase => "[Match Case]",
-      SearchTextKind::MatchWholeWord => "[Match Whole Word]",
-      SearchTextKind::Regex => "[Regex]",
-      SearchTextKind::MatchCaseWholeWord => "[Match Case Whole Word]",
+      SearchTextKind::Simple => "[Simple]".to_string(),
+      SearchTextKind::MatchCase => "[Match Case]".to_string(),
+      SearchTextKind::MatchWholeWord => "[Match Whole Word]".to_string(),
+      SearchTextKind::Regex => "[Regex]".to_string(),
+      SearchTextKind::MatchCaseWholeWord => "[Match Case Whole Word]".to_string(),
       #[cfg(feature = "ast_grep")]
-      SearchTextKind::AstGrep => "[AST Grep]",
+      SearchTextKind::AstGrep => {
+        match (state.ast_grep.rule_name(), &state.ast_grep.lang) {
+          (Some(rule_name), _) => format!("[AST Grep Rule: {rule_name}]"),
+          (None, Some(lang)) => format!("[AST Grep: {lang}]"),
+          (None, None) => "[AST Grep]".to_string(),
+        }
+      },
+    };
+
+    let search_kind = if state.include_file_paths { format!("[File Paths] {search_kind}") } else { search_kind };
+    let search_kind = match state.git.scope_label() {
+      Some(scope) => format!("[{scope}] {search_kind}"),
+      None => search_kind,
     };
 
     let block = Block::bordered()
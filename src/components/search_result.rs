@@ -15,8 +15,9 @@ use crate::{
   layout::get_layout,
   redux::{
     action::Action,
-    state::{FocusedScreen, SearchResultState, State},
+    state::{FocusedScreen, SearchResultKind, SearchResultState, State},
     thunk::ThunkAction,
+    utils::{relative_path, root_for_path},
   },
   tabs::Tab,
 };
@@ -108,6 +109,9 @@ impl SearchResult {
             path: selected_result.path.clone(),
             matches: selected_result.matches.clone(),
             total_matches: selected_result.total_matches,
+            kind: selected_result.kind,
+            content_hash: selected_result.content_hash,
+            removed: selected_result.removed,
           },
         });
         self.command_tx.as_ref().unwrap().send(action).unwrap();
@@ -152,6 +156,9 @@ impl SearchResult {
         path: selected_result.path.clone(),
         matches: selected_result.matches.clone(),
         total_matches: selected_result.total_matches,
+        kind: selected_result.kind,
+        content_hash: selected_result.content_hash,
+        removed: selected_result.removed,
       },
     });
     self.command_tx.as_ref().unwrap().send(action).unwrap();
@@ -171,6 +178,9 @@ impl SearchResult {
         path: selected_result.path.clone(),
         matches: selected_result.matches.clone(),
         total_matches: selected_result.total_matches,
+        kind: selected_result.kind,
+        content_hash: selected_result.content_hash,
+        removed: selected_result.removed,
       },
     });
     self.command_tx.as_ref().unwrap().send(action).unwrap();
@@ -327,7 +337,7 @@ impl Component for SearchResult {
       block
     };
 
-    let project_root = state.project_root.to_string_lossy();
+    let show_roots = state.project_roots.len() > 1;
     let results_to_display = &state.search_result.list;
     let search_term = self.search_input.value().to_lowercase();
 
@@ -335,10 +345,20 @@ impl Component for SearchResult {
       .iter()
       .enumerate()
       .map(|(index, s)| {
-        let path = s.path.strip_prefix(format!("{project_root}/").as_str()).unwrap_or(&s.path);
+        let project_root = root_for_path(&s.path, &state.project_roots);
+        let path = relative_path(&s.path, &project_root);
         let mut spans = Vec::new();
         let mut start = 0;
 
+        if show_roots {
+          let root_name = project_root.file_name().unwrap_or(project_root.as_os_str()).to_string_lossy();
+          spans.push(Span::styled(format!("{root_name}: "), Style::default().fg(Color::Cyan)));
+        }
+
+        if s.kind == SearchResultKind::FilePath {
+          spans.push(Span::styled("[path] ", Style::default().fg(Color::Magenta)));
+        }
+
         if !search_term.is_empty() {
           for (idx, _) in path.to_lowercase().match_indices(&search_term) {
             if start < idx {
@@ -356,6 +376,13 @@ impl Component for SearchResult {
           spans.push(Span::raw(&path[start..]));
         }
 
+        if s.removed {
+          let removed_style = Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT);
+          let mut spans: Vec<Span> = spans.into_iter().map(|span| span.patch_style(removed_style)).collect();
+          spans.push(Span::styled(" (removed)", Style::default().fg(Color::Red)));
+          return ListItem::new(Line::from(spans));
+        }
+
         spans.push(Span::raw(" ("));
         spans.push(Span::styled(s.total_matches.to_string(), Style::default().fg(Color::Yellow)));
         spans.push(Span::raw(")"));
@@ -364,6 +391,13 @@ impl Component for SearchResult {
       })
       .collect();
 
+    // A selection restored from a session arrives through the state before the list was navigated.
+    if self.state.selected().is_none() {
+      if let Some(index) = state.selected_result.index.filter(|index| *index < state.search_result.list.len()) {
+        self.state.select(Some(index));
+      }
+    }
+
     let internal_selected = self.state.selected().unwrap_or(0);
 
     let details_widget = List::new(list_items)
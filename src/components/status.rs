@@ -6,7 +6,7 @@ use std::{
 
 use color_eyre::eyre::Result;
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::prelude::*;
+use ratatui::{prelude::*, widgets::LineGauge};
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::{event, trace, Level};
@@ -18,7 +18,11 @@ use crate::{
   components::notifications::NotificationEnum,
   config::{Config, KeyBindings},
   layout::get_layout,
-  redux::{action::Action, state::State, thunk::ThunkAction},
+  redux::{
+    action::Action,
+    state::{ReplaceProgress, State},
+    thunk::ThunkAction,
+  },
   ripgrep::RipgrepOutput,
   tabs::Tab,
   ui::small_help_widget::SmallHelpWidget,
@@ -36,6 +40,25 @@ impl Status {
   pub fn new() -> Self {
     Self::default()
   }
+
+  fn progress_gauge(progress: &ReplaceProgress) -> LineGauge<'static> {
+    let ratio = if progress.total == 0 { 0.0 } else { progress.done as f64 / progress.total as f64 };
+    let bytes = format_bytes(progress.bytes);
+    let label = format!("Replacing {}/{} files, {bytes} written", progress.done, progress.total);
+    LineGauge::default()
+      .filled_style(Style::default().fg(Color::Yellow))
+      .unfilled_style(Style::default().fg(Color::DarkGray))
+      .label(label)
+      .ratio(ratio.min(1.0))
+  }
+}
+
+fn format_bytes(bytes: u64) -> String {
+  match bytes {
+    0..1024 => format!("{bytes} B"),
+    1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+    _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+  }
 }
 
 impl Component for Status {
@@ -49,6 +72,14 @@ impl Component for Status {
     Ok(())
   }
 
+  fn handle_key_events(&mut self, key: KeyEvent, state: &State) -> Result<Option<AppAction>> {
+    if key.code == KeyCode::Esc && state.replace_progress.is_some() {
+      let cancel_action = AppAction::Thunk(ThunkAction::CancelReplace);
+      self.command_tx.as_ref().unwrap().send(cancel_action).unwrap();
+    }
+    Ok(None)
+  }
+
   fn update(&mut self, action: AppAction) -> Result<Option<AppAction>> {
     if let AppAction::Tui(TuiAction::Status(content)) = action {
       self.content = content;
@@ -59,6 +90,11 @@ impl Component for Status {
   fn draw(&mut self, f: &mut Frame<'_>, area: Rect, state: &State) -> Result<()> {
     let layout = get_layout(area);
 
+    if let Some(progress) = &state.replace_progress {
+      f.render_widget(Self::progress_gauge(progress), layout.status_right);
+      return Ok(());
+    }
+
     let small_help = SmallHelpWidget::new(self.content.clone(), Color::Yellow, Alignment::Right);
     f.render_widget(small_help, layout.status_right);
     Ok(())
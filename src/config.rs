@@ -14,6 +14,7 @@ use serde_json::Value as JsonValue;
 use crate::{
   action::{AppAction, TuiAction},
   mode::Mode,
+  search_backend::ExternalSearchBackend,
 };
 
 const CONFIG: &str = include_str!("../.config/config.json5");
@@ -34,6 +35,26 @@ pub struct Config {
   pub keybindings: KeyBindings,
   #[serde(default)]
   pub styles: Styles,
+  /// Name of the search backend for text searches: `native`, `ripgrep` or one of `search_backends`.
+  #[serde(default)]
+  pub search_engine: String,
+  #[serde(default)]
+  pub search_backends: HashMap<String, ExternalSearchBackend>,
+  /// Commit the files changed by each replace, like `--commit`.
+  #[serde(default)]
+  pub auto_commit: bool,
+  /// Branch created for the replace commits, like `--commit-branch`.
+  #[serde(default)]
+  pub commit_branch: Option<String>,
+  /// Write the files of a replace all together or not at all, like `--transactional`.
+  #[serde(default)]
+  pub transactional_replace: bool,
+  /// Encoding of files without a byte order mark, like `--encoding`.
+  #[serde(default)]
+  pub encoding: Option<String>,
+  /// Search files again when they change on disk, like `--watch`.
+  #[serde(default)]
+  pub watch: bool,
 }
 
 impl Config {
@@ -0,0 +1,110 @@
+use std::{fs, io, path::Path};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// How a file's bytes map to text, kept to write the replaced text back the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileEncoding {
+  pub encoding: &'static Encoding,
+  pub bom: bool,
+}
+
+/// Encoding named on the command line or in the config file, `auto` leaves it to BOM sniffing like ripgrep's `-E`.
+pub fn parse_encoding(label: &str) -> Result<Option<&'static Encoding>, String> {
+  if label.eq_ignore_ascii_case("auto") {
+    return Ok(None);
+  }
+  Encoding::for_label(label.as_bytes()).map(Some).ok_or_else(|| format!("unknown encoding {label}"))
+}
+
+/// Decodes a file's content. A byte order mark decides the encoding, then the configured one, otherwise the content
+/// has to be UTF-8. Content with a NUL byte is binary, as ripgrep sees it, and never decoded.
+pub fn decode(bytes: &[u8], configured: Option<&'static Encoding>) -> io::Result<(String, FileEncoding)> {
+  let (encoding, bom_length) = match Encoding::for_bom(bytes) {
+    Some((encoding, bom_length)) => (encoding, bom_length),
+    None => (configured.unwrap_or(UTF_8), 0),
+  };
+  if bom_length == 0 && encoding.is_ascii_compatible() && bytes.contains(&0) {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "binary file"));
+  }
+
+  let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+  if had_errors {
+    let message = if bom_length == 0 && configured.is_none() {
+      "not valid UTF-8, set its encoding with --encoding".to_string()
+    } else {
+      format!("not valid {}", encoding.name())
+    };
+    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+  }
+  Ok((text.into_owned(), FileEncoding { encoding, bom: bom_length > 0 }))
+}
+
+/// Encodes text back to the file's encoding, failing rather than writing characters the encoding can't represent.
+pub fn encode(text: &str, file_encoding: FileEncoding) -> io::Result<Vec<u8>> {
+  let FileEncoding { encoding, bom } = file_encoding;
+  let mut bytes = Vec::with_capacity(text.len() + 3);
+
+  // encoding_rs only decodes UTF-16, its encoder would produce UTF-8.
+  if encoding == UTF_16LE || encoding == UTF_16BE {
+    if bom {
+      bytes.extend(if encoding == UTF_16LE { [0xFF, 0xFE] } else { [0xFE, 0xFF] });
+    }
+    for unit in text.encode_utf16() {
+      bytes.extend(if encoding == UTF_16LE { unit.to_le_bytes() } else { unit.to_be_bytes() });
+    }
+    return Ok(bytes);
+  }
+
+  if bom && encoding == UTF_8 {
+    bytes.extend([0xEF, 0xBB, 0xBF]);
+  }
+  let (encoded, _, had_errors) = encoding.encode(text);
+  if had_errors {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("replacement can't be written in {}", encoding.name()),
+    ));
+  }
+  bytes.extend_from_slice(&encoded);
+  Ok(bytes)
+}
+
+pub fn read_file(path: impl AsRef<Path>, configured: Option<&'static Encoding>) -> io::Result<(String, FileEncoding)> {
+  decode(&fs::read(path)?, configured)
+}
+
+pub fn write_file(path: impl AsRef<Path>, text: &str, file_encoding: FileEncoding) -> io::Result<()> {
+  fs::write(path, encode(text, file_encoding)?)
+}
+
+#[cfg(test)]
+mod tests {
+  use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let latin1 = b"caf\xe9 foo\n";
+    let (text, file_encoding) = decode(latin1, Some(WINDOWS_1252)).unwrap();
+    assert_eq!(text, "café foo\n");
+    assert_eq!(encode(&text.replace("foo", "bär"), file_encoding).unwrap(), b"caf\xe9 b\xe4r\n");
+    assert!(decode(latin1, None).is_err());
+
+    let utf16 = [0xFF, 0xFE, b'f', 0, b'o', 0, b'o', 0];
+    let (text, file_encoding) = decode(&utf16, None).unwrap();
+    assert_eq!(text, "foo");
+    assert_eq!(file_encoding, FileEncoding { encoding: UTF_16LE, bom: true });
+    assert_eq!(encode(&text, file_encoding).unwrap(), utf16);
+
+    let (_, file_encoding) = decode("日本".as_bytes(), None).unwrap();
+    assert_eq!(file_encoding, FileEncoding { encoding: UTF_8, bom: false });
+    let shift_jis = FileEncoding { encoding: SHIFT_JIS, bom: false };
+    assert_eq!(encode("日本", shift_jis).unwrap(), b"\x93\xfa\x96\x7b");
+    assert!(encode("café", FileEncoding { encoding: SHIFT_JIS, bom: false }).is_err());
+
+    assert_eq!(decode(b"foo\0bar", None).unwrap_err().to_string(), "binary file");
+  }
+}
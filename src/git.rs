@@ -0,0 +1,233 @@
+use std::{
+  collections::HashSet,
+  ffi::OsStr,
+  io::{self, Write},
+  path::{Path, PathBuf},
+  process::{Command, Stdio},
+};
+
+use crate::redux::state::GitScope;
+
+/// Maximum pathspecs per git invocation, keeping the command line well below the OS limit.
+const PATHSPEC_BATCH_SIZE: usize = 500;
+
+/// Files of `scope` under `paths`, listed by git. None for `GitScope::All`, which searches the paths themselves.
+pub fn scope_files(paths: &[PathBuf], scope: &GitScope) -> io::Result<Option<Vec<PathBuf>>> {
+  let mut files = Vec::new();
+  for path in paths {
+    // Git runs inside the path so it finds the path's repository, the pathspec narrows the listing to the path.
+    let (dir, pathspec) = match path.file_name() {
+      Some(file_name) if !path.is_dir() => (path.parent().unwrap_or(Path::new("")), Path::new(file_name)),
+      _ => (path.as_path(), Path::new(".")),
+    };
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+
+    let args: &[&str] = match scope {
+      GitScope::All => return Ok(None),
+      GitScope::Tracked => &["ls-files", "-z"],
+      GitScope::Modified => &["diff", "--name-only", "-z", "--relative", "--diff-filter=d", "HEAD"],
+      GitScope::ChangedSince(base_ref) => &["diff", "--name-only", "-z", "--relative", "--diff-filter=d", base_ref],
+    };
+    let stdout = git_output(dir, args, &[pathspec])?;
+    files.extend(stdout.split('\0').filter(|name| !name.is_empty()).map(|name| dir.join(name)).filter(|f| f.is_file()));
+  }
+  Ok(Some(files))
+}
+
+/// Of `files`, given relative to the repository at `root`, those with staged, unstaged or untracked changes.
+pub fn dirty_files(root: &Path, files: &[String]) -> io::Result<Vec<String>> {
+  let mut dirty = HashSet::new();
+  for batch in files.chunks(PATHSPEC_BATCH_SIZE) {
+    let changed = git_output(root, &["diff", "--name-only", "-z", "--relative", "HEAD"], batch)?;
+    let untracked = git_output(root, &["ls-files", "-z", "--others", "--exclude-standard"], batch)?;
+    dirty.extend(changed.split('\0').chain(untracked.split('\0')).filter(|name| !name.is_empty()).map(String::from));
+  }
+  Ok(files.iter().filter(|file| dirty.contains(*file)).cloned().collect())
+}
+
+/// Saves the changes of `files` to the git stash, then applies the stash again so they stay in the working tree.
+pub fn stash_files(root: &Path, files: &[String], message: &str) -> io::Result<()> {
+  for batch in files.chunks(PATHSPEC_BATCH_SIZE) {
+    git_output(root, &["stash", "push", "--include-untracked", "-m", message], batch)?;
+    git_output(root, &["stash", "apply", "--index"], &[] as &[&str])?;
+  }
+  Ok(())
+}
+
+/// Stages `files`, given relative to the repository at `root`, and commits them alone, leaving anything else that is
+/// staged out of the commit. With a `branch`, the commit goes to a new branch of that name unless it is already
+/// checked out, a taken name gets a numbered suffix. Returns the short hash, None when the files have no changes.
+pub fn commit_files(root: &Path, files: &[String], message: &str, branch: Option<&str>) -> io::Result<Option<String>> {
+  let mut staged = Vec::new();
+  for batch in files.chunks(PATHSPEC_BATCH_SIZE) {
+    // The old path of a `git mv` is gone from both the working tree and the index, `git add` rejects it.
+    let tracked = git_output(root, &["ls-files", "-z"], batch)?;
+    let addable: Vec<&String> = batch
+      .iter()
+      .filter(|file| root.join(file).exists() || tracked.split('\0').any(|name| name == file.as_str()))
+      .collect();
+    if !addable.is_empty() {
+      git_output(root, &["add", "-A"], &addable)?;
+    }
+    let changed = git_output(root, &["diff", "--cached", "--name-only", "-z", "--relative"], batch)?;
+    staged.extend(changed.split('\0').filter(|name| !name.is_empty()).map(String::from));
+  }
+  if staged.is_empty() {
+    return Ok(None);
+  }
+
+  if let Some(branch) = branch {
+    let current = git_output(root, &["rev-parse", "--abbrev-ref", "HEAD"], &[] as &[&str])?;
+    if current.trim() != branch {
+      let branch = unused_branch_name(root, branch);
+      git_output(root, &["checkout", "-q", "-b", &branch], &[] as &[&str])?;
+    }
+  }
+
+  // The pathspecs go through stdin, a large replace can stage more files than fit on a command line.
+  let mut child = Command::new("git")
+    .arg("-C")
+    .arg(root)
+    .args(["commit", "-q", "-m", message, "--pathspec-from-file=-", "--pathspec-file-nul"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .spawn()?;
+  child.stdin.take().expect("stdin is piped").write_all(staged.join("\0").as_bytes())?;
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+  }
+
+  let hash = git_output(root, &["rev-parse", "--short", "HEAD"], &[] as &[&str])?;
+  Ok(Some(hash.trim().to_string()))
+}
+
+fn unused_branch_name(root: &Path, branch: &str) -> String {
+  let exists = |name: &str| {
+    git_output(root, &["rev-parse", "--verify", "-q", &format!("refs/heads/{name}")], &[] as &[&str]).is_ok()
+  };
+  if !exists(branch) {
+    return branch.to_string();
+  }
+  (2..).map(|n| format!("{branch}-{n}")).find(|name| !exists(name)).expect("some suffix is free")
+}
+
+fn git_output(dir: &Path, args: &[&str], pathspecs: &[impl AsRef<OsStr>]) -> io::Result<String> {
+  let mut command = Command::new("git");
+  command.arg("-C").arg(dir).args(args);
+  if !pathspecs.is_empty() {
+    command.arg("--").args(pathspecs);
+  }
+
+  let output = command.output()?;
+  if !output.status.success() {
+    return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+      .arg("-C")
+      .arg(dir)
+      .args(["-c", "user.name=serpl", "-c", "user.email=serpl@example.com"])
+      .args(args)
+      .output()
+      .unwrap()
+      .status;
+    assert!(status.success());
+  }
+
+  #[test]
+  fn test_scope_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    git(&dir, &["init", "-q"]);
+    fs::write(dir.join(".gitignore"), "ignored.txt\n").unwrap();
+    fs::write(dir.join("ignored.txt"), "committed anyway\n").unwrap();
+    fs::write(dir.join("changed.txt"), "one\n").unwrap();
+    git(&dir, &["add", "-f", ".gitignore", "ignored.txt", "changed.txt"]);
+    git(&dir, &["commit", "-q", "-m", "initial"]);
+    fs::write(dir.join("changed.txt"), "two\n").unwrap();
+    fs::write(dir.join("untracked.txt"), "new\n").unwrap();
+
+    let paths = vec![dir.clone()];
+    let names = |scope: GitScope| {
+      let mut files: Vec<String> = scope_files(&paths, &scope)
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|file| file.strip_prefix(&dir).unwrap().to_string_lossy().to_string())
+        .collect();
+      files.sort();
+      files
+    };
+    assert_eq!(names(GitScope::Tracked), vec![".gitignore", "changed.txt", "ignored.txt"]);
+    assert_eq!(names(GitScope::Modified), vec!["changed.txt"]);
+    assert!(scope_files(&paths, &GitScope::All).unwrap().is_none());
+  }
+
+  #[test]
+  fn test_commit_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    git(&dir, &["init", "-q"]);
+    git(&dir, &["config", "user.name", "serpl"]);
+    git(&dir, &["config", "user.email", "serpl@example.com"]);
+    fs::write(dir.join("replaced.txt"), "old\n").unwrap();
+    fs::write(dir.join("staged.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "initial"]);
+    fs::write(dir.join("staged.txt"), "two\n").unwrap();
+    git(&dir, &["add", "staged.txt"]);
+
+    let files = vec!["replaced.txt".to_string()];
+    assert_eq!(commit_files(&dir, &files, "serpl: replace", Some("serpl")).unwrap(), None);
+
+    fs::write(dir.join("replaced.txt"), "new\n").unwrap();
+    git(&dir, &["mv", "replaced.txt", "renamed.txt"]);
+    let files = vec!["replaced.txt".to_string(), "renamed.txt".to_string()];
+    let hash = commit_files(&dir, &files, "serpl: replace", Some("serpl")).unwrap().unwrap();
+    let show = |args: &[&str]| git_output(&dir, args, &[] as &[&str]).unwrap().trim().to_string();
+    assert_eq!(show(&["rev-parse", "--short", "HEAD"]), hash);
+    assert_eq!(show(&["rev-parse", "--abbrev-ref", "HEAD"]), "serpl");
+    assert_eq!(show(&["show", "--name-only", "--format=", "HEAD"]), "renamed.txt\nreplaced.txt");
+    assert_eq!(dirty_files(&dir, &["staged.txt".to_string()]).unwrap(), vec!["staged.txt"]);
+  }
+
+  #[test]
+  fn test_dirty_and_stash_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    git(&dir, &["init", "-q"]);
+    git(&dir, &["config", "user.name", "serpl"]);
+    git(&dir, &["config", "user.email", "serpl@example.com"]);
+    fs::write(dir.join("changed.txt"), "one\n").unwrap();
+    fs::write(dir.join("clean.txt"), "one\n").unwrap();
+    git(&dir, &["add", "."]);
+    git(&dir, &["commit", "-q", "-m", "initial"]);
+    fs::write(dir.join("changed.txt"), "two\n").unwrap();
+    fs::write(dir.join("untracked.txt"), "new\n").unwrap();
+
+    let files = vec!["changed.txt".to_string(), "clean.txt".to_string(), "untracked.txt".to_string()];
+    let dirty = dirty_files(&dir, &files).unwrap();
+    assert_eq!(dirty, vec!["changed.txt", "untracked.txt"]);
+
+    stash_files(&dir, &dirty, "serpl: before replacing one").unwrap();
+    let show = |args: &[&str]| git_output(&dir, args, &[] as &[&str]).unwrap().trim().to_string();
+    assert!(show(&["stash", "list", "--format=%s"]).ends_with(": serpl: before replacing one"));
+    assert_eq!(show(&["show", "--format=", "stash@{0}^3:untracked.txt"]), "new");
+    assert_eq!(fs::read_to_string(dir.join("changed.txt")).unwrap(), "two\n");
+    assert_eq!(fs::read_to_string(dir.join("untracked.txt")).unwrap(), "new\n");
+    assert_eq!(dirty_files(&dir, &files).unwrap(), dirty);
+  }
+}
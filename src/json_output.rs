@@ -0,0 +1,228 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::redux::{
+  state::{Match, Metadata, ReplaceTextKind, SearchResultKind, SearchResultState, State},
+  utils::{apply_replace_captures, relative_path, root_for_path, try_get_replace_regex},
+};
+
+/// One line of the `--json` output: a line per file with matches, then a summary line. The schema is documented in
+/// the README and only ever grows new fields.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JsonLine {
+  File(JsonFile),
+  Summary { metadata: Metadata },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct JsonFile {
+  pub path: String,
+  pub kind: JsonResultKind,
+  pub total_matches: usize,
+  pub matches: Vec<JsonMatch>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonResultKind {
+  Content,
+  FilePath,
+}
+
+/// Matched lines of a file, or the file's path relative to its project root for `file_path` results.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct JsonMatch {
+  pub line_number: usize,
+  /// Byte offset of `text` in the file.
+  pub absolute_offset: usize,
+  pub text: String,
+  pub submatches: Vec<JsonSubMatch>,
+  /// `text` with every submatch replaced, None without a replace text.
+  pub replacement: Option<String>,
+}
+
+/// A match within `text`, as byte offsets into it.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct JsonSubMatch {
+  pub start: usize,
+  pub end: usize,
+  pub text: String,
+  pub replacement: Option<String>,
+}
+
+/// The search results of `state` as JSON lines, the proposed replacements computed from its replace text.
+pub fn json_lines(state: &State) -> Vec<JsonLine> {
+  let mut lines: Vec<JsonLine> = state
+    .search_result
+    .list
+    .iter()
+    .filter(|result| !result.removed)
+    .map(|result| JsonLine::File(json_file(result, state)))
+    .collect();
+  lines.push(JsonLine::Summary { metadata: state.search_result.metadata.clone() });
+  lines
+}
+
+pub fn write_json_lines(state: &State, out: &mut impl Write) -> io::Result<()> {
+  for line in json_lines(state) {
+    serde_json::to_writer(&mut *out, &line)?;
+    writeln!(out)?;
+  }
+  out.flush()
+}
+
+pub fn json_file(result: &SearchResultState, state: &State) -> JsonFile {
+  let kind = match result.kind {
+    SearchResultKind::Content => JsonResultKind::Content,
+    SearchResultKind::FilePath => JsonResultKind::FilePath,
+  };
+  let path = match result.kind {
+    SearchResultKind::Content => result.path.clone(),
+    SearchResultKind::FilePath => {
+      let project_root = root_for_path(&result.path, &state.project_roots);
+      relative_path(&result.path, &project_root).to_string()
+    },
+  };
+  JsonFile {
+    path,
+    kind,
+    total_matches: result.total_matches,
+    matches: result.matches.iter().map(|m| json_match(m, state)).collect(),
+  }
+}
+
+fn json_match(m: &Match, state: &State) -> JsonMatch {
+  let text = m.lines.as_ref().map(|lines| lines.text.clone()).unwrap_or_default();
+
+  // Submatches locate themselves by line within the match and column within that line, ripgrep's always on line 0.
+  let line_offsets: Vec<usize> = std::iter::once(0)
+    .chain(text.split_inclusive('\n').scan(0, |offset, line| {
+      *offset += line.len();
+      Some(*offset)
+    }))
+    .collect();
+  let first_line = m.submatches.first().map_or(0, |submatch| submatch.line_start);
+  let offset = |line: usize, column: usize| {
+    let line_offset = line_offsets.get(line.saturating_sub(first_line)).copied().unwrap_or(text.len());
+    (line_offset + column).min(text.len())
+  };
+  let ranges: Vec<(usize, usize)> = m
+    .submatches
+    .iter()
+    .map(|submatch| (offset(submatch.line_start, submatch.start), offset(submatch.line_end, submatch.end)))
+    .collect();
+
+  let (replacement, submatch_replacements) = replacements(m, &text, &ranges, state);
+  let submatches = ranges
+    .iter()
+    .zip(submatch_replacements)
+    .map(|(&(start, end), replacement)| {
+      JsonSubMatch { start, end, text: text.get(start..end).unwrap_or_default().to_string(), replacement }
+    })
+    .collect();
+
+  // An AST grep match starts at its node, the offset of the text is that of the node's first line. Its line numbers
+  // are 0-based, as ast-grep reports them.
+  let (line_number, absolute_offset) = match &m.byte_range {
+    Some(_) => (m.line_number + 1, m.absolute_offset.saturating_sub(ranges.first().map_or(0, |range| range.0))),
+    None => (m.line_number, m.absolute_offset),
+  };
+  JsonMatch { line_number, absolute_offset, text, submatches, replacement }
+}
+
+/// The replaced text of a match and of each of its submatches.
+fn replacements(
+  m: &Match,
+  text: &str,
+  ranges: &[(usize, usize)],
+  state: &State,
+) -> (Option<String>, Vec<Option<String>>) {
+  let none = || (None, vec![None; ranges.len()]);
+  let replace_text_state = &state.replace_text;
+
+  #[cfg(feature = "ast_grep")]
+  if replace_text_state.kind == ReplaceTextKind::AstGrep {
+    let (Some(replacement), Some(&(start, end))) = (&m.replacement, ranges.first()) else {
+      return none();
+    };
+    let replaced = format!("{}{replacement}{}", &text[..start], &text[end..]);
+    return (Some(replaced), vec![Some(replacement.clone())]);
+  }
+
+  if replace_text_state.kind == ReplaceTextKind::DeleteLine {
+    return (Some(String::new()), vec![Some(String::new()); ranges.len()]);
+  }
+  if replace_text_state.text.is_empty() {
+    return none();
+  }
+  let Ok(re) = try_get_replace_regex(&state.search_text, replace_text_state) else {
+    return none();
+  };
+
+  let replace = |caps: &regex::Captures| apply_replace_captures(caps, &state.search_text.kind, replace_text_state);
+  let replaced = re.replace_all(text, replace).into_owned();
+  let submatch_replacements = ranges
+    .iter()
+    .map(|&(start, _)| {
+      re.captures_at(text, start).filter(|caps| caps.get(0).unwrap().start() == start).map(|caps| replace(&caps))
+    })
+    .collect();
+  (Some(replaced), submatch_replacements)
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::{
+    redux::state::{ReplaceTextState, SearchListState, SearchTextKind, SearchTextState, SubMatch},
+    ripgrep::RipgrepLines,
+  };
+
+  #[test]
+  fn test_write_json_lines() {
+    let state = State {
+      search_text: SearchTextState { text: r"(\w+)_id".to_string(), kind: SearchTextKind::Regex },
+      replace_text: ReplaceTextState { text: "${1}Id".to_string(), kind: ReplaceTextKind::Simple },
+      search_result: SearchListState {
+        list: vec![SearchResultState {
+          index: Some(0),
+          path: "src/user.rs".to_string(),
+          matches: vec![Match {
+            line_number: 3,
+            lines: Some(RipgrepLines { text: "let user_id = group_id;\n".to_string() }),
+            absolute_offset: 40,
+            submatches: vec![SubMatch { start: 4, end: 11, line_start: 0, line_end: 0 }, SubMatch {
+              start: 14,
+              end: 22,
+              line_start: 0,
+              line_end: 0,
+            }],
+            ..Default::default()
+          }],
+          total_matches: 2,
+          ..Default::default()
+        }],
+        metadata: Metadata { elapsed_time: 5, matched_lines: 1, matches: 2, searches: 1, searches_with_match: 1 },
+      },
+      ..State::new(vec![".".into()])
+    };
+
+    let mut out = Vec::new();
+    write_json_lines(&state, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines, vec![
+      concat!(
+        r#"{"type":"file","path":"src/user.rs","kind":"content","total_matches":2,"matches":[{"line_number":3,"#,
+        r#""absolute_offset":40,"text":"let user_id = group_id;\n","submatches":[{"start":4,"end":11,"#,
+        r#""text":"user_id","replacement":"userId"},{"start":14,"end":22,"text":"group_id","replacement":"groupId"}],"#,
+        r#""replacement":"let userId = groupId;\n"}]}"#
+      ),
+      r#"{"type":"summary","metadata":{"elapsed_time":5,"matched_lines":1,"matches":2,"searches":1,"searches_with_match":1}}"#,
+    ]);
+  }
+}
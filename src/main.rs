@@ -5,20 +5,33 @@
 pub mod action;
 pub mod app;
 pub mod astgrep;
+pub mod change_set;
 pub mod cli;
 pub mod components;
 pub mod config;
+pub mod encoding;
+pub mod git;
+pub mod json_output;
 pub mod layout;
 pub mod macros;
 pub mod mode;
+pub mod quickfix;
 pub mod redux;
 pub mod ripgrep;
+pub mod rpc;
+pub mod search_backend;
+pub mod session;
 pub mod tabs;
+pub mod transaction;
 pub mod tui;
 pub mod ui;
 pub mod utils;
+pub mod watcher;
 
-use std::process::Command;
+use std::{
+  path::{Path, PathBuf},
+  process::Command,
+};
 
 use clap::Parser;
 use cli::Cli;
@@ -26,7 +39,9 @@ use color_eyre::eyre::{eyre, Result};
 use log::LevelFilter;
 
 use crate::{
-  app::App,
+  app::{App, AppOptions},
+  change_set::ChangeSet,
+  redux::state::ReplaceOutcome,
   utils::{initialize_logging, initialize_panic_handler, version},
 };
 
@@ -34,15 +49,31 @@ fn check_dependency(command: &str) -> bool {
   Command::new(command).arg("--version").output().is_ok()
 }
 
-async fn tokio_main() -> Result<()> {
-  // let _ = simple_logging::log_to_file("serpl.log", LevelFilter::Info);
+/// Applies a change set to the first project root, printing what happened to each file. Fails when any file
+/// conflicts, in which case none was changed.
+fn apply_change_set(change_set_file: &Path, project_roots: &[PathBuf]) -> Result<()> {
+  let change_set = ChangeSet::load(change_set_file)
+    .map_err(|e| eyre!("Failed to read change set {}: {}", change_set_file.display(), e))?;
+  let results = change_set.apply(project_roots);
+  for result in &results {
+    match &result.outcome {
+      ReplaceOutcome::Replaced => println!("Applied {}", result.path),
+      ReplaceOutcome::Skipped(reason) => println!("Skipped {}: {reason}", result.path),
+      ReplaceOutcome::Failed(reason) => println!("Failed {}: {reason}", result.path),
+    }
+  }
 
-  if !check_dependency("rg") {
-    eprintln!("\x1b[31mError: ripgrep (rg) is not installed. Please install it to use serpl.\x1b[0m");
-    return Err(eyre!("ripgrep is not installed"));
+  let unapplied = results.iter().filter(|result| result.outcome != ReplaceOutcome::Replaced).count();
+  if unapplied > 0 {
+    return Err(eyre!("{unapplied} of {} files could not be applied, no file was changed", results.len()));
   }
+  Ok(())
+}
 
-  #[cfg(feature = "ast_grep")]
+async fn tokio_main() -> Result<()> {
+  // let _ = simple_logging::log_to_file("serpl.log", LevelFilter::Info);
+
+  #[cfg(all(feature = "ast_grep", not(feature = "ast_grep_native")))]
   if !check_dependency("ast-grep") {
     eprintln!("\x1b[31mError: ast-grep is not installed. Please install it to use serpl with AST features.\x1b[0m");
     return Err(eyre!("ast-grep is not installed"));
@@ -50,8 +81,36 @@ async fn tokio_main() -> Result<()> {
   initialize_panic_handler()?;
 
   let args = Cli::parse();
-  let mut app = App::new(args.project_root)?;
-  app.run().await?;
+  if let Some(change_set_file) = &args.apply_changes {
+    return apply_change_set(change_set_file, &args.project_root);
+  }
+  let search_files = args.search_files()?;
+  let ast_grep = args.ast_grep_state()?;
+  let git = args.git_state();
+  let query = args.query_options();
+
+  // The native search covers patterns only, rule files still need the ast-grep binary.
+  #[cfg(feature = "ast_grep_native")]
+  if ast_grep.rule_file.is_some() && !check_dependency("ast-grep") {
+    eprintln!("\x1b[31mError: ast-grep is not installed. Please install it to search with rule files.\x1b[0m");
+    return Err(eyre!("ast-grep is not installed"));
+  }
+  let options = AppOptions {
+    transactional_replace: args.transactional,
+    encoding: args.encoding,
+    watch: args.watch,
+    resume: args.resume,
+    query,
+    listen: args.listen,
+  };
+  let mut app = App::new(args.project_root, search_files, ast_grep, git, options)?;
+  if args.json {
+    app.print_json().await?;
+  } else if args.quickfix.is_some() || args.edit {
+    app.write_quickfix(args.quickfix.as_deref(), args.edit).await?;
+  } else {
+    app.run().await?;
+  }
 
   Ok(())
 }
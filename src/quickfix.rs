@@ -0,0 +1,113 @@
+use std::{
+  io::{self, Write},
+  path::Path,
+  process::Command,
+};
+
+use crate::{
+  json_output::json_file,
+  redux::state::{SearchResultKind, SearchResultState, State},
+};
+
+/// Where the interface exports the quickfix list, in the working directory.
+pub const QUICKFIX_FILE: &str = "serpl-quickfix.txt";
+
+/// A match as a quickfix list entry, with its 1-based byte column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuickfixEntry {
+  pub path: String,
+  pub line: usize,
+  pub column: usize,
+  pub text: String,
+}
+
+/// An entry per match of a result, none for a file path match.
+pub fn result_entries(result: &SearchResultState, state: &State) -> Vec<QuickfixEntry> {
+  if result.kind != SearchResultKind::Content {
+    return Vec::new();
+  }
+  let mut entries = Vec::new();
+  for m in json_file(result, state).matches {
+    for submatch in &m.submatches {
+      let before = &m.text[..submatch.start];
+      let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+      entries.push(QuickfixEntry {
+        path: result.path.clone(),
+        line: m.line_number + before.matches('\n').count(),
+        column: submatch.start - line_start + 1,
+        text: m.text[line_start..].lines().next().unwrap_or_default().to_string(),
+      });
+    }
+  }
+  entries
+}
+
+/// The listed matches in Vim's quickfix format, `path:line:col: text`, one line per match. Removed files and lines
+/// are left out, and so are file path matches.
+pub fn quickfix_lines(state: &State) -> Vec<String> {
+  state
+    .search_result
+    .list
+    .iter()
+    .filter(|result| !result.removed)
+    .flat_map(|result| result_entries(result, state))
+    .map(|entry| format!("{}:{}:{}: {}", entry.path, entry.line, entry.column, entry.text))
+    .collect()
+}
+
+pub fn write_quickfix(state: &State, out: &mut impl Write) -> io::Result<()> {
+  for line in quickfix_lines(state) {
+    writeln!(out, "{line}")?;
+  }
+  out.flush()
+}
+
+/// Opens `$VISUAL` or `$EDITOR`, falling back to vi, on the quickfix file with `-q` and waits for it to exit.
+pub fn open_editor(quickfix_file: &Path) -> io::Result<()> {
+  let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+  let mut words = editor.split_whitespace();
+  let program = words.next().unwrap_or("vi");
+  let status = Command::new(program).args(words).arg("-q").arg(quickfix_file).status()?;
+  if !status.success() {
+    return Err(io::Error::other(format!("{editor} exited with {status}")));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::{
+    redux::state::{Match, SearchListState, SubMatch},
+    ripgrep::RipgrepLines,
+  };
+
+  #[test]
+  fn test_quickfix_lines() {
+    let submatch = |start| SubMatch { start, end: start + 7, line_start: 0, line_end: 0 };
+    let state = State {
+      search_result: SearchListState {
+        list: vec![
+          SearchResultState {
+            path: "src/user.rs".to_string(),
+            matches: vec![Match {
+              line_number: 3,
+              lines: Some(RipgrepLines { text: "let user_id = group_id;\nuser_id\n".to_string() }),
+              submatches: vec![submatch(4), submatch(24)],
+              ..Default::default()
+            }],
+            total_matches: 2,
+            ..Default::default()
+          },
+          SearchResultState { path: "src/gone.rs".to_string(), removed: true, ..Default::default() },
+        ],
+        ..Default::default()
+      },
+      ..State::new(vec![".".into()])
+    };
+
+    assert_eq!(quickfix_lines(&state), vec!["src/user.rs:3:5: let user_id = group_id;", "src/user.rs:4:1: user_id"]);
+  }
+}
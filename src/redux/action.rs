@@ -1,4 +1,4 @@
-use std::{fmt, string::ToString};
+use std::{fmt, path::PathBuf, string::ToString};
 
 use ratatui::style::Color;
 use serde::{
@@ -9,7 +9,10 @@ use strum::Display;
 
 use crate::{
   mode::Mode,
-  redux::state::{Dialog, FocusedScreen, ReplaceTextKind, SearchListState, SearchResultState, SearchTextKind},
+  redux::state::{
+    Dialog, DirtyFilesChoice, FocusedScreen, GitScope, ReplaceProgress, ReplaceTextKind, SearchListState,
+    SearchResultState, SearchTextKind,
+  },
   tabs::Tab,
 };
 
@@ -21,15 +24,26 @@ pub enum Action {
   SetReplaceText { text: String },
   SetSearchTextKind { kind: SearchTextKind },
   SetReplaceTextKind { kind: ReplaceTextKind },
+  SetIncludeFilePaths { include: bool },
+  SetAstGrepLanguage { lang: Option<String> },
+  SetGitScope { scope: GitScope },
+  SetDirtyFilesChoice { choice: Option<DirtyFilesChoice> },
+  RemoveFilesFromList { paths: Vec<String> },
+  ExcludeFile { path: String },
+  ExcludeLine { path: String, line_number: usize },
+  DropRemovedResults,
   SetActiveTab { tab: Tab },
   LoopOverTabs,
   BackLoopOverTabs,
   ChangeMode { mode: Mode },
   SetGlobalLoading { global_loading: bool },
+  SetReplaceProgress { progress: Option<ReplaceProgress> },
   ResetState,
   SetNotification { message: String, show: bool, ttl: u64, color: Color },
   SetDialog { dialog: Option<Dialog> },
   SetFocusedScreen { screen: Option<FocusedScreen> },
   RemoveFileFromList { index: usize },
+  ConfirmProjectRoot { root: PathBuf },
+  RemoveProjectRootFromList { root: PathBuf },
   RemoveLineFromFile { file_index: usize, line_index: usize },
 }
@@ -5,24 +5,32 @@ use regex::Regex;
 use super::{action::Action, state::State};
 use crate::{
   mode::Mode,
-  redux::state::{
-    Dialog, FocusedScreen, NotificationState, ReplaceTextState, SearchListState, SearchResultState, SearchTextKind,
-    SearchTextState,
+  redux::{
+    state::{
+      AstGrepState, Dialog, ExcludedLine, Exclusions, FocusedScreen, GitState, NotificationState, ReplaceTextState,
+      SearchListState, SearchResultState, SearchTextKind, SearchTextState,
+    },
+    utils::root_for_path,
   },
   tabs::Tab,
 };
 
 pub fn reducer(state: State, action: Action) -> State {
   match action {
-    Action::SetSearchList { search_list } => State { search_result: search_list, ..state },
+    Action::SetSearchList { mut search_list } => {
+      state.exclusions.apply(&mut search_list.list);
+      State { search_result: search_list, ..state }
+    },
     Action::SetSelectedResult { result } => State { selected_result: result, ..state },
     Action::SetSearchText { text } => {
       let is_dialog_visible = check_dialog_visible(&state);
       if is_dialog_visible {
         return state;
       }
+      // Exclusions were picked from the results of the previous text.
+      let exclusions = if text == state.search_text.text { state.exclusions.clone() } else { Exclusions::default() };
       let search_kind = &state.search_text.kind;
-      State { search_text: SearchTextState { text, kind: *search_kind }, ..state }
+      State { search_text: SearchTextState { text, kind: *search_kind }, exclusions, ..state }
     },
     Action::SetReplaceText { text } => {
       let is_dialog_visible = check_dialog_visible(&state);
@@ -46,6 +54,27 @@ pub fn reducer(state: State, action: Action) -> State {
       }
       State { replace_text: ReplaceTextState { kind, text: state.replace_text.text.clone() }, ..state }
     },
+    Action::SetIncludeFilePaths { include } => {
+      let is_dialog_visible = check_dialog_visible(&state);
+      if is_dialog_visible {
+        return state;
+      }
+      State { include_file_paths: include, ..state }
+    },
+    Action::SetAstGrepLanguage { lang } => {
+      let is_dialog_visible = check_dialog_visible(&state);
+      if is_dialog_visible {
+        return state;
+      }
+      State { ast_grep: AstGrepState { lang, ..state.ast_grep.clone() }, ..state }
+    },
+    Action::SetGitScope { scope } => {
+      let is_dialog_visible = check_dialog_visible(&state);
+      if is_dialog_visible {
+        return state;
+      }
+      State { git: GitState { scope, ..state.git.clone() }, ..state }
+    },
     Action::SetActiveTab { tab } => {
       let is_dialog_visible = check_dialog_visible(&state);
       if is_dialog_visible {
@@ -109,7 +138,19 @@ pub fn reducer(state: State, action: Action) -> State {
     },
     Action::ChangeMode { mode } => State { mode, ..state },
     Action::SetGlobalLoading { global_loading } => State { global_loading, ..state },
-    Action::ResetState => State::new(state.project_root.clone()),
+    Action::SetReplaceProgress { progress } => State { replace_progress: progress, ..state },
+    Action::ResetState => {
+      State {
+        search_files: state.search_files.clone(),
+        ast_grep: state.ast_grep.clone(),
+        git: state.git.clone(),
+        search_engine: state.search_engine.clone(),
+        search_backends: state.search_backends.clone(),
+        transactional_replace: state.transactional_replace,
+        encoding: state.encoding,
+        ..State::new(state.project_roots.clone())
+      }
+    },
     Action::SetNotification { message, show, ttl, color } => {
       State { notification: NotificationState { message, show, ttl, color }, ..state }
     },
@@ -121,6 +162,8 @@ pub fn reducer(state: State, action: Action) -> State {
         focused_screen: match temporary_dialog {
           Some(Dialog::ConfirmGitDirectory(_)) => FocusedScreen::ConfirmGitDirectoryDialog,
           Some(Dialog::ConfirmReplace(_)) => FocusedScreen::ConfirmReplaceDialog,
+          Some(Dialog::ConfirmDirtyFiles(_)) => FocusedScreen::ConfirmDirtyFilesDialog,
+          Some(Dialog::ReplaceSummary(_)) => FocusedScreen::ReplaceSummaryDialog,
           Some(Dialog::HelpDialog(_)) => FocusedScreen::HelpDialog,
           _ => state.focused_screen,
         },
@@ -139,6 +182,50 @@ pub fn reducer(state: State, action: Action) -> State {
       }
       State { search_result: new_search_result, ..state }
     },
+    Action::ConfirmProjectRoot { root } => {
+      let mut confirmed_roots = state.confirmed_roots.clone();
+      if !confirmed_roots.contains(&root) {
+        confirmed_roots.push(root);
+      }
+      State { confirmed_roots, ..state }
+    },
+    Action::SetDirtyFilesChoice { choice } => State { dirty_files_choice: choice, ..state },
+    Action::RemoveFilesFromList { paths } => {
+      let mut new_search_result = state.search_result.clone();
+      new_search_result.list.retain(|result| !paths.contains(&result.path));
+      for (index, result) in new_search_result.list.iter_mut().enumerate() {
+        result.index = Some(index);
+      }
+      let new_selected_result = new_search_result.list.first().cloned().unwrap_or_default();
+      State { search_result: new_search_result, selected_result: new_selected_result, ..state }
+    },
+    Action::ExcludeFile { path } => {
+      let mut exclusions = state.exclusions.clone();
+      exclusions.files.push(path);
+      State { exclusions, ..state }
+    },
+    Action::ExcludeLine { path, line_number } => {
+      let mut exclusions = state.exclusions.clone();
+      exclusions.lines.push(ExcludedLine { path, line_number });
+      State { exclusions, ..state }
+    },
+    Action::DropRemovedResults => {
+      let mut new_search_result = state.search_result.clone();
+      new_search_result.list.retain(|result| !result.removed);
+      for (index, result) in new_search_result.list.iter_mut().enumerate() {
+        result.index = Some(index);
+      }
+      State { search_result: new_search_result, ..state }
+    },
+    Action::RemoveProjectRootFromList { root } => {
+      let mut new_search_result = state.search_result.clone();
+      new_search_result.list.retain(|result| root_for_path(&result.path, &state.project_roots) != root);
+      for (index, result) in new_search_result.list.iter_mut().enumerate() {
+        result.index = Some(index);
+      }
+      let new_selected_result = new_search_result.list.first().cloned().unwrap_or_default();
+      State { search_result: new_search_result, selected_result: new_selected_result, ..state }
+    },
 
     Action::RemoveLineFromFile { file_index, line_index } => {
       let mut new_search_result = state.search_result.clone();
@@ -170,13 +257,43 @@ pub fn reducer(state: State, action: Action) -> State {
   }
 }
 
-fn check_dialog_visible(state: &State) -> bool {
+pub fn check_dialog_visible(state: &State) -> bool {
   match &state.dialog {
     Some(dialog) => match dialog {
       Dialog::ConfirmGitDirectory(dialog) => dialog.show,
       Dialog::ConfirmReplace(dialog) => dialog.show,
+      Dialog::ConfirmDirtyFiles(dialog) => dialog.show,
+      Dialog::ReplaceSummary(dialog) => dialog.show,
       Dialog::HelpDialog(dialog) => dialog.show,
     },
     None => false,
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_remove_project_root_from_list() {
+    let roots = vec![PathBuf::from("app"), PathBuf::from("lib")];
+    let result = |path: &str| SearchResultState { path: path.to_string(), ..Default::default() };
+    let state = State {
+      search_result: SearchListState {
+        list: vec![result("app/a.rs"), result("lib/b.rs"), result("app/c.rs"), result("lib/d.rs")],
+        ..Default::default()
+      },
+      ..State::new(roots)
+    };
+
+    let state = reducer(state, Action::RemoveProjectRootFromList { root: PathBuf::from("app") });
+    let remaining: Vec<(String, Option<usize>)> =
+      state.search_result.list.iter().map(|result| (result.path.clone(), result.index)).collect();
+    assert_eq!(remaining, vec![("lib/b.rs".to_string(), Some(0)), ("lib/d.rs".to_string(), Some(1))]);
+    assert_eq!(state.selected_result.path, "lib/b.rs");
+  }
+}
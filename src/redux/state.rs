@@ -5,10 +5,14 @@ use std::{
   time::{Duration, SystemTime},
 };
 
+use encoding_rs::Encoding;
 use ratatui::style::Color;
 use serde::{Deserialize, Serialize};
 
-use crate::{mode::Mode, ripgrep::RipgrepLines, tabs::Tab};
+use crate::{
+  astgrep::ReplacementOffsets, mode::Mode, redux::thunk::ThunkAction, ripgrep::RipgrepLines,
+  search_backend::ExternalSearchBackend, tabs::Tab,
+};
 
 #[derive(Default, Clone, PartialEq, Debug)]
 pub struct State {
@@ -21,11 +25,28 @@ pub struct State {
   pub global_loading: bool,
   pub notification: NotificationState,
   pub dialog: Option<Dialog>,
-  pub project_root: PathBuf,
+  pub project_roots: Vec<PathBuf>,
+  pub confirmed_roots: Vec<PathBuf>,
+  /// How the replace treats files with uncommitted changes, None until the user was asked.
+  pub dirty_files_choice: Option<DirtyFilesChoice>,
+  pub search_files: Option<Vec<PathBuf>>,
   pub focused_screen: FocusedScreen,
   pub previous_focused_screen: FocusedScreen,
   pub help_dialog_visible: bool,
   pub is_large_folder: bool,
+  pub include_file_paths: bool,
+  pub ast_grep: AstGrepState,
+  pub git: GitState,
+  pub search_engine: String,
+  pub search_backends: HashMap<String, ExternalSearchBackend>,
+  /// Write the files of a replace all together or not at all.
+  pub transactional_replace: bool,
+  /// Encoding of files without a byte order mark, UTF-8 when None.
+  pub encoding: Option<&'static Encoding>,
+  /// Files written so far by the running replace, None when no replace runs.
+  pub replace_progress: Option<ReplaceProgress>,
+  /// Files and lines removed from the results with `d`, left out of every search for the same text.
+  pub exclusions: Exclusions,
 }
 
 #[derive(Default, Clone, PartialEq, Eq, Debug)]
@@ -37,6 +58,8 @@ pub enum FocusedScreen {
   Preview,
   ConfirmGitDirectoryDialog,
   ConfirmReplaceDialog,
+  ConfirmDirtyFilesDialog,
+  ReplaceSummaryDialog,
   HelpDialog,
 }
 
@@ -69,18 +92,165 @@ pub enum ReplaceTextKind {
   #[default]
   Simple,
   PreserveCase,
+  SmartCase,
   DeleteLine,
   #[cfg(feature = "ast_grep")]
   AstGrep,
 }
 
+/// Options for AST grep mode: the `--lang` passed to ast-grep, or a rule file that replaces the typed pattern.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AstGrepState {
+  pub lang: Option<String>,
+  pub rule_file: Option<PathBuf>,
+}
+
+impl AstGrepState {
+  pub fn rule_name(&self) -> Option<String> {
+    self.rule_file.as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy().to_string())
+  }
+}
+
+/// Git options: which files of the repositories under the search paths are searched, and whether replacing commits.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GitState {
+  pub scope: GitScope,
+  /// Ref given with `--changed-since`, offered by the scope toggle.
+  pub base_ref: Option<String>,
+  /// Commit the files changed by each replace in their repository.
+  pub auto_commit: bool,
+  /// Branch created for the replace commits, the current branch when None.
+  pub commit_branch: Option<String>,
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum GitScope {
+  /// Every file the search walks, git is not involved.
+  #[default]
+  All,
+  /// Files tracked by git, including committed files that are ignored locally.
+  Tracked,
+  /// Files whose working tree differs from HEAD.
+  Modified,
+  /// Files whose working tree differs from the ref.
+  ChangedSince(String),
+}
+
+impl GitState {
+  /// Scope following the current one in the scope toggle, `changed since` is offered only when a base ref was given.
+  pub fn next_scope(&self) -> GitScope {
+    match (&self.scope, &self.base_ref) {
+      (GitScope::All, _) => GitScope::Tracked,
+      (GitScope::Tracked, _) => GitScope::Modified,
+      (GitScope::Modified, Some(base_ref)) => GitScope::ChangedSince(base_ref.clone()),
+      (GitScope::Modified, None) | (GitScope::ChangedSince(_), _) => GitScope::All,
+    }
+  }
+
+  /// Label for the search block title, None when the scope is not restricted.
+  pub fn scope_label(&self) -> Option<String> {
+    match &self.scope {
+      GitScope::All => None,
+      GitScope::Tracked => Some("Tracked Files".to_string()),
+      GitScope::Modified => Some("Modified Files".to_string()),
+      GitScope::ChangedSince(base_ref) => Some(format!("Changed Since {base_ref}")),
+    }
+  }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum Dialog {
   ConfirmGitDirectory(ConfirmDialogState),
   ConfirmReplace(ConfirmDialogState),
+  ConfirmDirtyFiles(DirtyFilesDialogState),
+  ReplaceSummary(ReplaceSummaryDialogState),
   HelpDialog(HelpDialogState),
 }
 
+/// Files about to be replaced in that have uncommitted changes in git.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DirtyFilesDialogState {
+  pub files: Vec<String>,
+  /// The replace that asked, dispatched again once the user chose.
+  pub replace: ThunkAction,
+  pub show: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DirtyFilesChoice {
+  /// Replace in the files anyway, mixing the replacement with the uncommitted changes.
+  Proceed,
+  /// Save the files' changes to the git stash first, keeping them in the working tree.
+  Stash,
+}
+
+/// Files of the last replace that were skipped or failed, shown after it finishes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReplaceSummaryDialogState {
+  pub results: Vec<FileReplaceResult>,
+  pub show: bool,
+}
+
+/// What a replace did to one file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FileReplaceResult {
+  pub path: String,
+  pub outcome: ReplaceOutcome,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ReplaceOutcome {
+  Replaced,
+  /// Left untouched on purpose, e.g. because the file is gone, binary or can't be decoded.
+  Skipped(String),
+  /// Reading, replacing or writing the file went wrong.
+  Failed(String),
+}
+
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Exclusions {
+  pub files: Vec<String>,
+  pub lines: Vec<ExcludedLine>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExcludedLine {
+  pub path: String,
+  pub line_number: usize,
+}
+
+impl Exclusions {
+  pub fn is_empty(&self) -> bool {
+    self.files.is_empty() && self.lines.is_empty()
+  }
+
+  /// Drops the excluded files and the matches on excluded lines from the results, then files left without matches.
+  pub fn apply(&self, list: &mut Vec<SearchResultState>) {
+    if self.is_empty() {
+      return;
+    }
+    list.retain(|result| !self.files.contains(&result.path));
+    for result in list.iter_mut().filter(|result| result.kind == SearchResultKind::Content) {
+      let is_excluded = |m: &Match| self.lines.iter().any(|l| l.path == result.path && l.line_number == m.line_number);
+      if result.matches.iter().any(is_excluded) {
+        result.matches.retain(|m| !is_excluded(m));
+        result.total_matches = result.matches.iter().map(|m| m.submatches.len()).sum();
+      }
+    }
+    list.retain(|result| !result.matches.is_empty() || result.removed);
+    for (index, result) in list.iter_mut().enumerate() {
+      result.index = Some(index);
+    }
+  }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReplaceProgress {
+  pub done: usize,
+  pub total: usize,
+  pub bytes: u64,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct HelpDialogState {
   pub show: bool,
@@ -101,6 +271,8 @@ pub struct ConfirmDialogState {
 pub enum DialogAction {
   ConfirmReplace,
   CancelReplace,
+  ConfirmRoot(PathBuf),
+  SkipRoot(PathBuf),
 }
 
 #[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq)]
@@ -131,6 +303,19 @@ pub struct SearchResultState {
   pub path: String,
   pub matches: Vec<Match>,
   pub total_matches: usize,
+  pub kind: SearchResultKind,
+  /// Hash of the file when an AST grep search read it, guarding the replacement offsets stored in its matches.
+  pub content_hash: Option<u64>,
+  /// Deleted or no longer matching since the search, found by watch mode. Kept in place until the next search.
+  pub removed: bool,
+}
+
+/// Whether a result matched inside the file or in the file's path, the latter being renamed on replace.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchResultKind {
+  #[default]
+  Content,
+  FilePath,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
@@ -142,6 +327,19 @@ pub struct Match {
   pub absolute_offset: usize,
   pub submatches: Vec<SubMatch>,
   pub replacement: Option<String>,
+  pub replacement_offsets: Option<ReplacementOffsets>,
+  /// Byte range of the node an AST grep match covers, identifying the match when replacing.
+  pub byte_range: Option<std::ops::Range<usize>>,
+  pub meta_variables: Vec<MetaVariableBinding>,
+}
+
+/// A meta variable captured by an AST grep match, such as `$A` or `$$$ARGS`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct MetaVariableBinding {
+  pub name: String,
+  pub text: String,
+  /// Start and end `(line, column)` of the capture, zero based. Transformed variables have no range.
+  pub range: Option<((usize, usize), (usize, usize))>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
@@ -153,7 +351,28 @@ pub struct SubMatch {
 }
 
 impl State {
-  pub fn new(project_root: PathBuf) -> Self {
-    Self { project_root, is_large_folder: false, ..Default::default() }
+  pub fn new(project_roots: Vec<PathBuf>) -> Self {
+    Self { project_roots, is_large_folder: false, ..Default::default() }
+  }
+
+  /// Whether the search comes from a loaded ast-grep rule instead of the typed search and replace text.
+  pub fn uses_ast_grep_rule(&self) -> bool {
+    #[cfg(feature = "ast_grep")]
+    if self.search_text.kind == SearchTextKind::AstGrep {
+      return self.ast_grep.rule_file.is_some();
+    }
+    false
+  }
+
+  pub fn has_search_query(&self) -> bool {
+    self.uses_ast_grep_rule() || !self.search_text.text.is_empty()
+  }
+
+  /// Paths handed to the search tools: the explicit file list when one was given, otherwise the project roots.
+  pub fn search_paths(&self) -> Vec<PathBuf> {
+    match &self.search_files {
+      Some(files) => files.clone(),
+      None => self.project_roots.clone(),
+    }
   }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use redux_rs::{middlewares::thunk::Thunk, StoreApi};
 use tokio::sync::mpsc::UnboundedSender;
@@ -6,24 +6,32 @@ use tokio::sync::mpsc::UnboundedSender;
 use super::{action::Action, state::State};
 use crate::action::{AppAction, TuiAction};
 
+pub mod cancel_replace;
+pub mod export_change_set;
+pub mod export_quickfix;
 pub mod process_line_replace;
 pub mod process_replace;
 pub mod process_search;
 pub mod process_single_file_replace;
+pub mod refresh_files;
 pub mod remove_file_from_list;
 pub mod remove_line_from_file;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ThunkAction {
   ProcessSearch,
   ProcessReplace(ForceReplace),
+  CancelReplace,
   RemoveFileFromList(usize),
   RemoveLineFromFile(usize, usize),
   ProcessSingleFileReplace(usize),
   ProcessLineReplace(usize, usize),
+  RefreshFiles(Vec<PathBuf>),
+  ExportChangeSet,
+  ExportQuickfix,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ForceReplace(pub bool);
 
 pub fn thunk_impl<Api>(
@@ -34,16 +42,20 @@ where
   Api: StoreApi<State, Action> + Send + Sync + 'static,
 {
   match action {
-    ThunkAction::ProcessSearch => Box::new(process_search::ProcessSearchThunk::new()),
+    ThunkAction::ProcessSearch => Box::new(process_search::ProcessSearchThunk::new(command_tx)),
     ThunkAction::ProcessReplace(force_replace) => {
       Box::new(process_replace::ProcessReplaceThunk::new(command_tx, force_replace))
     },
+    ThunkAction::CancelReplace => Box::new(cancel_replace::CancelReplaceThunk::new()),
     ThunkAction::ProcessSingleFileReplace(index) => {
       Box::new(process_single_file_replace::ProcessSingleFileReplaceThunk::new(command_tx, index))
     },
     ThunkAction::ProcessLineReplace(file_index, line_index) => {
       Box::new(process_line_replace::ProcessLineReplaceThunk::new(command_tx, file_index, line_index))
     },
+    ThunkAction::RefreshFiles(paths) => Box::new(refresh_files::RefreshFilesThunk::new(paths)),
+    ThunkAction::ExportChangeSet => Box::new(export_change_set::ExportChangeSetThunk::new(command_tx)),
+    ThunkAction::ExportQuickfix => Box::new(export_quickfix::ExportQuickfixThunk::new(command_tx)),
     ThunkAction::RemoveFileFromList(index) => Box::new(remove_file_from_list::RemoveFileFromListThunk::new(index)),
     ThunkAction::RemoveLineFromFile(file_index, line_index) => {
       Box::new(remove_line_from_file::RemoveLineFromFileThunk::new(file_index, line_index))
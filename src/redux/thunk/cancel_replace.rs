@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use redux_rs::{middlewares::thunk::Thunk, StoreApi};
+
+use crate::redux::{action::Action, state::State, thunk::process_replace::cancel_replace};
+
+pub struct CancelReplaceThunk {}
+
+impl CancelReplaceThunk {
+  pub fn new() -> Self {
+    Self {}
+  }
+}
+
+impl Default for CancelReplaceThunk {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[async_trait]
+impl<Api> Thunk<State, Action, Api> for CancelReplaceThunk
+where
+  Api: StoreApi<State, Action> + Send + Sync + 'static,
+{
+  async fn execute(&self, store: Arc<Api>) {
+    if store.select(|state: &State| state.replace_progress.is_some()).await {
+      log::info!("Cancelling the running replace");
+      cancel_replace();
+    }
+  }
+}
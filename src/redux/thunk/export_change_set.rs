@@ -0,0 +1,55 @@
+use std::{path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use redux_rs::{middlewares::thunk::Thunk, StoreApi};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+  action::{AppAction, TuiAction},
+  change_set::{ChangeSet, CHANGE_SET_FILE},
+  components::notifications::NotificationEnum,
+  redux::{action::Action, state::State},
+};
+
+pub struct ExportChangeSetThunk {
+  command_tx: Arc<UnboundedSender<AppAction>>,
+}
+
+impl ExportChangeSetThunk {
+  pub fn new(command_tx: Arc<UnboundedSender<AppAction>>) -> Self {
+    Self { command_tx }
+  }
+}
+
+#[async_trait]
+impl<Api> Thunk<State, Action, Api> for ExportChangeSetThunk
+where
+  Api: StoreApi<State, Action> + Send + Sync + 'static,
+{
+  async fn execute(&self, store: Arc<Api>) {
+    let state = store.state_cloned().await;
+    let path = Path::new(CHANGE_SET_FILE);
+    let renames = ChangeSet::left_out_renames(&state);
+    let left_out = if renames > 0 { format!(", {renames} file renames left out") } else { String::new() };
+    let notification = match ChangeSet::from_state(&state).and_then(|change_set| {
+      change_set.save(path)?;
+      Ok(change_set)
+    }) {
+      Ok(change_set) if change_set.files.is_empty() && renames > 0 => {
+        NotificationEnum::Info("No replacements to export, file renames can't be exported".to_string())
+      },
+      Ok(change_set) if change_set.files.is_empty() => {
+        NotificationEnum::Info("No replacements to export, enter a replace text first".to_string())
+      },
+      Ok(change_set) => {
+        let (edits, files) = (change_set.edit_count(), change_set.files.len());
+        NotificationEnum::Info(format!("Exported {edits} changes in {files} files to {CHANGE_SET_FILE}{left_out}"))
+      },
+      Err(e) => {
+        log::error!("Failed to export the change set: {}", e);
+        NotificationEnum::Error(format!("Failed to export the change set: {e}"))
+      },
+    };
+    self.command_tx.send(AppAction::Tui(TuiAction::Notify(notification))).unwrap();
+  }
+}
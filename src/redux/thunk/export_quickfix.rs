@@ -0,0 +1,41 @@
+use std::{fs, sync::Arc};
+
+use async_trait::async_trait;
+use redux_rs::{middlewares::thunk::Thunk, StoreApi};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+  action::{AppAction, TuiAction},
+  components::notifications::NotificationEnum,
+  quickfix::{quickfix_lines, QUICKFIX_FILE},
+  redux::{action::Action, state::State},
+};
+
+pub struct ExportQuickfixThunk {
+  command_tx: Arc<UnboundedSender<AppAction>>,
+}
+
+impl ExportQuickfixThunk {
+  pub fn new(command_tx: Arc<UnboundedSender<AppAction>>) -> Self {
+    Self { command_tx }
+  }
+}
+
+#[async_trait]
+impl<Api> Thunk<State, Action, Api> for ExportQuickfixThunk
+where
+  Api: StoreApi<State, Action> + Send + Sync + 'static,
+{
+  async fn execute(&self, store: Arc<Api>) {
+    let lines = store.select(quickfix_lines).await;
+    let content: String = lines.iter().map(|line| format!("{line}\n")).collect();
+    let notification = match fs::write(QUICKFIX_FILE, content) {
+      Ok(()) => NotificationEnum::Info(format!("Wrote {} matches to {QUICKFIX_FILE}", lines.len())),
+      Err(e) => {
+        log::error!("Failed to write the quickfix list: {}", e);
+        NotificationEnum::Error(format!("Failed to write the quickfix list: {e}"))
+      },
+    };
+    self.command_tx.send(AppAction::Tui(TuiAction::Notify(notification))).unwrap();
+  }
+}
@@ -1,6 +1,8 @@
-use std::{fs, process::Command, sync::Arc};
+use std::{fs, io, path::PathBuf, process::Command, sync::Arc};
 
 use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use encoding_rs::Encoding;
 use redux_rs::{middlewares::thunk::Thunk, StoreApi};
 use regex::RegexBuilder;
 use serde_json::from_str;
@@ -10,11 +12,17 @@ use crate::{
   action::{AppAction, TuiAction},
   astgrep::AstGrepOutput,
   components::notifications::NotificationEnum,
+  encoding,
   redux::{
     action::Action,
-    state::{Match, ReplaceTextKind, ReplaceTextState, SearchTextKind, SearchTextState, State},
-    thunk::ThunkAction,
-    utils::{apply_replace, get_search_regex},
+    state::{
+      AstGrepState, Match, ReplaceTextKind, ReplaceTextState, SearchResultKind, SearchTextKind, SearchTextState, State,
+    },
+    thunk::{
+      process_replace::{commit_replace, confirm_dirty_files},
+      ThunkAction,
+    },
+    utils::{apply_replace_captures, get_replace_regex, rename_file_path, replace_match_ast, root_for_path},
   },
 };
 
@@ -29,63 +37,60 @@ impl ProcessLineReplaceThunk {
     Self { command_tx, file_index, line_index }
   }
 
-  async fn process_replace_line(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+  async fn process_replace_line(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Result<()> {
     let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
 
     if let Some(search_result) = search_list.list.get(self.file_index) {
+      if search_result.kind == SearchResultKind::FilePath {
+        let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+        let project_root = root_for_path(&search_result.path, &project_roots);
+        rename_file_path(search_result, &search_text_state, &replace_text_state, &project_root)
+          .map_err(|e| eyre!("Failed to rename {}: {e}", search_result.path))?;
+        return Ok(());
+      }
+
       if let Some(match_info) = search_result.matches.get(self.line_index) {
         let file_path = &search_result.path;
 
         #[cfg(feature = "ast_grep")]
         if search_text_state.kind == SearchTextKind::AstGrep {
-          self
-            .process_ast_grep_replace(
-              file_path,
-              &search_text_state.text,
-              &replace_text_state.text,
-              match_info.line_number,
-            )
-            .await;
-        } else {
-          process_normal_replace(search_text_state, match_info, replace_text_state, file_path);
+          let ast_grep_state = store.select(|state: &State| state.ast_grep.clone()).await;
+          return self.process_ast_grep_replace(store, &search_text_state, &replace_text_state, &ast_grep_state).await;
         }
 
-        #[cfg(not(feature = "ast_grep"))]
-        process_normal_replace(search_text_state, match_info, replace_text_state, file_path);
+        let encoding = store.select(|state: &State| state.encoding).await;
+        process_normal_replace(search_text_state, match_info, replace_text_state, file_path, encoding)
+          .map_err(|e| eyre!("Failed to replace in {file_path}: {e}"))?;
       }
     }
+    Ok(())
   }
 
+  /// Replaces only the selected match, then stores the shifted offsets of the file's other matches so later
+  /// replaces still hit them.
   async fn process_ast_grep_replace(
     &self,
-    file_path: &str,
-    search_pattern: &str,
-    replace_pattern: &str,
-    line_number: usize,
-  ) {
-    let output = Command::new("ast-grep")
-      .args(["run", "-p", search_pattern, "-r", replace_pattern, "--json=compact", file_path])
-      .output()
-      .expect("Failed to execute ast-grep for replacement");
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let ast_grep_results: Vec<AstGrepOutput> = from_str(&stdout).expect("Failed to parse ast-grep output");
-
-    let mut content = fs::read_to_string(file_path).expect("Unable to read file");
-
-    for result in ast_grep_results.iter().rev() {
-      if result.range.start.line == line_number {
-        if let (Some(replacement), Some(offsets)) = (&result.replacement, &result.replacement_offsets) {
-          let start = offsets.start;
-          let end = offsets.end;
-          content.replace_range(start..end, replacement);
-        }
-      }
-    }
-
-    fs::write(file_path, content).expect("Unable to write file");
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    search_text_state: &SearchTextState,
+    replace_text_state: &ReplaceTextState,
+    ast_grep_state: &AstGrepState,
+  ) -> Result<()> {
+    let mut search_list = store.select(|state: &State| state.search_result.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
+    let Some(search_result) = search_list.list.get_mut(self.file_index) else {
+      return Ok(());
+    };
+
+    replace_match_ast(search_result, self.line_index, search_text_state, replace_text_state, ast_grep_state, encoding)
+      .map_err(|e| eyre!("AST grep replace failed for {}: {e}", search_result.path))?;
+
+    store.dispatch(Action::SetSearchList { search_list }).await;
+    Ok(())
   }
 }
 
@@ -94,8 +99,9 @@ fn process_normal_replace(
   match_info: &Match,
   replace_text_state: ReplaceTextState,
   file_path: &str,
-) {
-  let content = fs::read_to_string(file_path).expect("Unable to read file");
+  encoding: Option<&'static Encoding>,
+) -> io::Result<()> {
+  let (content, file_encoding) = encoding::read_file(file_path, encoding)?;
   let mut lines: Vec<String> = content.lines().map(String::from).collect();
 
   if replace_text_state.kind == ReplaceTextKind::DeleteLine {
@@ -103,19 +109,18 @@ fn process_normal_replace(
       lines.remove(match_info.line_number - 1);
     }
   } else {
-    let re = get_search_regex(&search_text_state.text, &search_text_state.kind);
+    let re = get_replace_regex(&search_text_state, &replace_text_state);
 
     if let Some(line) = lines.get_mut(match_info.line_number - 1) {
       let replaced_line = re.replace_all(line, |caps: &regex::Captures| {
-        let matched_text = caps.get(0).unwrap().as_str();
-        apply_replace(matched_text, &replace_text_state.text, &replace_text_state.kind)
+        apply_replace_captures(caps, &search_text_state.kind, &replace_text_state)
       });
       *line = replaced_line.into_owned();
     }
   }
 
   let new_content = lines.join("\n");
-  fs::write(file_path, new_content).expect("Unable to write file");
+  encoding::write_file(file_path, &new_content, file_encoding)
 }
 
 #[async_trait]
@@ -124,18 +129,53 @@ where
   Api: StoreApi<State, Action> + Send + Sync + 'static,
 {
   async fn execute(&self, store: Arc<Api>) {
+    if store.select(|state: &State| state.replace_progress.is_some()).await {
+      let error_action =
+        AppAction::Tui(TuiAction::Notify(NotificationEnum::Error("A replace is already running".to_string())));
+      self.command_tx.send(error_action).unwrap();
+      return;
+    }
+    let file_index = self.file_index;
+    let paths: Vec<String> = store
+      .select(move |state: &State| state.search_result.list.get(file_index).map(|r| r.path.clone()))
+      .await
+      .into_iter()
+      .collect();
+    let replace = ThunkAction::ProcessLineReplace(self.file_index, self.line_index);
+    if !confirm_dirty_files(&store, &self.command_tx, &paths, replace).await {
+      return;
+    }
+
     let processing_status_action = AppAction::Tui(TuiAction::Status("Processing line replacement...".to_string()));
     self.command_tx.send(processing_status_action).unwrap();
 
-    self.process_replace_line(&store).await;
+    if let Err(e) = self.process_replace_line(&store).await {
+      log::error!("{}", e);
+      let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(e.to_string())));
+      self.command_tx.send(error_action).unwrap();
+      let done_processing_status_action = AppAction::Tui(TuiAction::Status("".to_string()));
+      self.command_tx.send(done_processing_status_action).unwrap();
+      return;
+    }
+
+    let result = store.select(move |state: &State| state.search_result.list.get(file_index).cloned()).await;
+    let (replaced, renamed) = match result {
+      Some(result) if result.kind == SearchResultKind::FilePath => (vec![], vec![result.path]),
+      Some(result) => (vec![result.path], vec![]),
+      None => (vec![], vec![]),
+    };
+    let commits = commit_replace(&store, &self.command_tx, &replaced, &renamed).await;
 
     store.dispatch(Action::RemoveLineFromFile { file_index: self.file_index, line_index: self.line_index }).await;
 
     let done_processing_status_action = AppAction::Tui(TuiAction::Status("".to_string()));
     self.command_tx.send(done_processing_status_action).unwrap();
 
-    let notification_action =
-      AppAction::Tui(TuiAction::Notify(NotificationEnum::Info("Line replacement completed successfully".to_string())));
+    let mut message = "Line replacement completed successfully".to_string();
+    if !commits.is_empty() {
+      message.push_str(&format!(", committed {}", commits.join(", ")));
+    }
+    let notification_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Info(message)));
     self.command_tx.send(notification_action).unwrap();
   }
 }
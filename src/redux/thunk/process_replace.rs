@@ -1,4 +1,15 @@
-use std::{collections::HashSet, fs, io::Write, path::PathBuf, process::Command, sync::Arc, time::Duration};
+use std::{
+  collections::HashSet,
+  fs,
+  io::{self, Write},
+  path::{Path, PathBuf},
+  process::Command,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
 
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
@@ -9,21 +20,144 @@ use redux_rs::{
 };
 use regex::RegexBuilder;
 use serde_json::from_str;
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::mpsc::{self, UnboundedSender};
 
 use crate::{
   action::{AppAction, TuiAction},
   astgrep::AstGrepOutput,
   components::notifications::NotificationEnum,
+  git,
   redux::{
     action::Action,
-    state::{ConfirmDialogState, Dialog, DialogAction, ReplaceTextKind, SearchTextKind, State},
+    state::{
+      AstGrepState, ConfirmDialogState, Dialog, DialogAction, DirtyFilesChoice, DirtyFilesDialogState,
+      FileReplaceResult, ReplaceOutcome, ReplaceProgress, ReplaceSummaryDialogState, ReplaceTextKind, ReplaceTextState,
+      SearchListState, SearchResultKind, SearchResultState, SearchTextKind, SearchTextState, State,
+    },
     thunk::{ForceReplace, ThunkAction},
-    utils::{get_search_regex, replace_file_ast, replace_file_normal},
+    utils::{
+      file_replace_result, get_renamed_path, relative_path, rename_file_path, replaced_content_ast_from_search,
+      replaced_content_normal, replaced_contents_ast, root_for_path, AST_GREP_BATCH_SIZE,
+    },
   },
+  transaction::FileTransaction,
   utils::is_git_repo,
 };
 
+/// Reason given for the files a transactional replace left untouched because another file couldn't be replaced.
+const ROLLED_BACK: &str = "not replaced, another file of the transaction failed";
+
+/// Reason given for the files a replace didn't reach before the user cancelled it.
+const CANCELLED: &str = "replace cancelled";
+
+/// Set by Esc while a replace writes files, checked before each file.
+static REPLACE_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Stops the running replace after the file it is writing.
+pub fn cancel_replace() {
+  REPLACE_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// A replace running on a blocking thread, sending its progress to the status bar after each file and checking
+/// before each file whether the user cancelled it.
+struct ReplaceRun<'a> {
+  progress: ReplaceProgress,
+  progress_tx: UnboundedSender<ReplaceProgress>,
+  cancelled: &'a AtomicBool,
+}
+
+impl<'a> ReplaceRun<'a> {
+  fn new(total: usize, progress_tx: UnboundedSender<ReplaceProgress>, cancelled: &'a AtomicBool) -> Self {
+    Self { progress: ReplaceProgress { total, ..Default::default() }, progress_tx, cancelled }
+  }
+
+  fn is_cancelled(&self) -> bool {
+    self.cancelled.load(Ordering::SeqCst)
+  }
+
+  fn file_done(&mut self, bytes: u64) {
+    self.progress.done += 1;
+    self.progress.bytes += bytes;
+    let _ = self.progress_tx.send(self.progress);
+  }
+
+  /// Writes a file's new content, or counts it as done with the reason it has none.
+  fn write(&mut self, path: &str, content: Result<Vec<u8>, ReplaceOutcome>) -> FileReplaceResult {
+    match content {
+      Ok(content) => {
+        let result = fs::write(path, &content);
+        self.file_done(if result.is_ok() { content.len() as u64 } else { 0 });
+        file_replace_result(path, result)
+      },
+      Err(outcome) => {
+        self.file_done(0);
+        FileReplaceResult { path: path.to_string(), outcome }
+      },
+    }
+  }
+
+  /// Writes the content `replaced_content` computes for each result until done or cancelled. Results not reached
+  /// before cancelling are skipped.
+  fn replace_each(
+    &mut self,
+    search_results: &[SearchResultState],
+    mut replaced_content: impl FnMut(&SearchResultState) -> io::Result<Vec<u8>>,
+  ) -> Vec<FileReplaceResult> {
+    search_results
+      .iter()
+      .map(|search_result| {
+        if self.is_cancelled() {
+          return cancelled_result(&search_result.path);
+        }
+        let content =
+          replaced_content(search_result).map_err(|e| file_replace_result(&search_result.path, Err(e)).outcome);
+        self.write(&search_result.path, content)
+      })
+      .collect()
+  }
+}
+
+fn cancelled_result(path: &str) -> FileReplaceResult {
+  FileReplaceResult { path: path.to_string(), outcome: ReplaceOutcome::Skipped(CANCELLED.to_string()) }
+}
+
+/// Writes the new contents in one transaction, or none of them when a file can't be read or written. Skipped files
+/// are left out of the transaction, they don't stop the others.
+fn commit_contents(
+  contents: Vec<(String, Result<Vec<u8>, ReplaceOutcome>)>,
+  run: &mut ReplaceRun,
+) -> Vec<FileReplaceResult> {
+  let mut transaction = FileTransaction::new();
+  let mut results = Vec::new();
+  for (path, content) in contents {
+    let outcome = match content {
+      Ok(content) => {
+        transaction.stage(&path, content);
+        ReplaceOutcome::Replaced
+      },
+      Err(outcome) => {
+        run.file_done(0);
+        outcome
+      },
+    };
+    results.push(FileReplaceResult { path, outcome });
+  }
+
+  let unreadable = results.iter().any(|result| matches!(result.outcome, ReplaceOutcome::Failed(_)));
+  let failed = if unreadable { None } else { transaction.commit_each(|_, bytes| run.file_done(bytes as u64)).err() };
+  if unreadable || failed.is_some() {
+    for result in results.iter_mut().filter(|result| result.outcome == ReplaceOutcome::Replaced) {
+      result.outcome = match &failed {
+        Some((failed_path, e)) if failed_path.as_path() == Path::new(&result.path) => {
+          ReplaceOutcome::Failed(e.to_string())
+        },
+        _ => ReplaceOutcome::Skipped(ROLLED_BACK.to_string()),
+      };
+    }
+  }
+  results
+}
+
 pub struct ProcessReplaceThunk {
   command_tx: Arc<UnboundedSender<AppAction>>,
   force_replace: ForceReplace,
@@ -34,29 +168,193 @@ impl ProcessReplaceThunk {
     Self { command_tx, force_replace }
   }
 
-  async fn process_ast_grep_replace(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+  /// Files unchanged since the search reuse its replacements, the rest go through batched ast-grep runs.
+  async fn process_ast_grep_replace(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Vec<FileReplaceResult> {
     let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let ast_grep_state = store.select(|state: &State| state.ast_grep.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
 
-    for search_result in &search_list.list {
-      replace_file_ast(search_result, &search_text_state, &replace_text_state);
-    }
+    let content_results: Vec<SearchResultState> =
+      search_list.list.into_iter().filter(|r| r.kind == SearchResultKind::Content).collect();
+    let paths = content_results.iter().map(|r| r.path.clone()).collect();
+    self
+      .run_blocking(store, paths, move |run| {
+        let mut results = Vec::new();
+        let mut stale_results = Vec::new();
+        for search_result in &content_results {
+          if run.is_cancelled() {
+            results.push(cancelled_result(&search_result.path));
+            continue;
+          }
+          match replaced_content_ast_from_search(search_result, encoding) {
+            Ok(Some(content)) => results.push(run.write(&search_result.path, Ok(content))),
+            Ok(None) => stale_results.push(search_result),
+            Err(e) => {
+              results
+                .push(run.write(&search_result.path, Err(file_replace_result(&search_result.path, Err(e)).outcome)))
+            },
+          }
+        }
+        for batch in stale_results.chunks(AST_GREP_BATCH_SIZE) {
+          if run.is_cancelled() {
+            results.extend(batch.iter().map(|search_result| cancelled_result(&search_result.path)));
+            continue;
+          }
+          for (path, content) in
+            replaced_contents_ast(batch, &search_text_state, &replace_text_state, &ast_grep_state, encoding)
+          {
+            results.push(run.write(&path, content));
+          }
+        }
+        results
+      })
+      .await
   }
 
-  async fn process_normal_replace(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+  /// Computes the new content of every file before writing any, then writes them in one transaction. A file that
+  /// can't be read or written leaves all of them untouched, and so does cancelling before the transaction commits.
+  async fn process_transactional_replace(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Vec<FileReplaceResult> {
     let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let ast_grep_state = store.select(|state: &State| state.ast_grep.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
+
+    let content_results: Vec<SearchResultState> =
+      search_list.list.into_iter().filter(|r| r.kind == SearchResultKind::Content).collect();
+    let paths = content_results.iter().map(|r| r.path.clone()).collect();
+    self
+      .run_blocking(store, paths, move |run| {
+        let paths: Vec<String> = content_results.iter().map(|r| r.path.clone()).collect();
+        let cancelled = || paths.iter().map(|path| cancelled_result(path)).collect();
+        #[cfg(feature = "ast_grep")]
+        let is_ast_grep = search_text_state.kind == SearchTextKind::AstGrep;
+        #[cfg(not(feature = "ast_grep"))]
+        let is_ast_grep = false;
+
+        let mut contents: Vec<(String, Result<Vec<u8>, ReplaceOutcome>)> = Vec::new();
+        let mut stale_results = Vec::new();
+        for search_result in &content_results {
+          if run.is_cancelled() {
+            return cancelled();
+          }
+          let content = if is_ast_grep {
+            match replaced_content_ast_from_search(search_result, encoding) {
+              Ok(Some(content)) => Ok(content),
+              Ok(None) => {
+                stale_results.push(search_result);
+                continue;
+              },
+              Err(e) => Err(file_replace_result(&search_result.path, Err(e)).outcome),
+            }
+          } else {
+            replaced_content_normal(search_result, &search_text_state, &replace_text_state, encoding)
+              .map_err(|e| file_replace_result(&search_result.path, Err(e)).outcome)
+          };
+          contents.push((search_result.path.clone(), content));
+        }
+        for batch in stale_results.chunks(AST_GREP_BATCH_SIZE) {
+          if run.is_cancelled() {
+            return cancelled();
+          }
+          for (path, content) in
+            replaced_contents_ast(batch, &search_text_state, &replace_text_state, &ast_grep_state, encoding)
+          {
+            contents.push((path, content));
+          }
+        }
+
+        commit_contents(contents, run)
+      })
+      .await
+  }
+
+  /// Writes the files one at a time, so cancelling keeps the files already written and skips the rest.
+  async fn process_normal_replace(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Vec<FileReplaceResult> {
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
+    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+    let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
+
+    let content_results: Vec<SearchResultState> =
+      search_list.list.into_iter().filter(|r| r.kind == SearchResultKind::Content).collect();
+    let paths = content_results.iter().map(|r| r.path.clone()).collect();
+    self
+      .run_blocking(store, paths, move |run| {
+        run.replace_each(&content_results, |search_result| {
+          replaced_content_normal(search_result, &search_text_state, &replace_text_state, encoding)
+        })
+      })
+      .await
+  }
 
-    let processing_status_action = AppAction::Tui(TuiAction::Status("Processing search and replace..".to_string()));
-    self.command_tx.send(processing_status_action).unwrap();
+  /// Runs `replace` on a blocking thread, showing the progress it reports in the status bar until it returns. A
+  /// replace that panics fails every file, which of them it wrote is unknown.
+  async fn run_blocking(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    paths: Vec<String>,
+    replace: impl FnOnce(&mut ReplaceRun<'static>) -> Vec<FileReplaceResult> + Send + 'static,
+  ) -> Vec<FileReplaceResult> {
+    let total = paths.len();
+    store
+      .dispatch(Action::SetReplaceProgress { progress: Some(ReplaceProgress { total, ..Default::default() }) })
+      .await;
 
-    let re = get_search_regex(&search_text_state.text, &search_text_state.kind);
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let replace = tokio::task::spawn_blocking(move || {
+      let mut run = ReplaceRun::new(total, progress_tx, &REPLACE_CANCELLED);
+      replace(&mut run)
+    });
 
-    for search_result in &search_list.list {
-      replace_file_normal(search_result, &search_text_state, &replace_text_state);
+    while let Some(mut progress) = progress_rx.recv().await {
+      while let Ok(latest) = progress_rx.try_recv() {
+        progress = latest;
+      }
+      store.dispatch(Action::SetReplaceProgress { progress: Some(progress) }).await;
     }
+
+    replace.await.unwrap_or_else(|e| {
+      log::error!("Replace task failed: {e}");
+      let outcome = ReplaceOutcome::Failed(format!("replace task failed: {e}"));
+      paths.into_iter().map(|path| FileReplaceResult { path, outcome: outcome.clone() }).collect()
+    })
+  }
+
+  async fn process_file_path_renames(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Vec<FileReplaceResult> {
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
+    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+    let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+
+    search_list
+      .list
+      .iter()
+      .filter(|r| r.kind == SearchResultKind::FilePath)
+      .map(|search_result| {
+        let project_root = root_for_path(&search_result.path, &project_roots);
+        let result = rename_file_path(search_result, &search_text_state, &replace_text_state, &project_root);
+        let outcome = match result {
+          Ok(()) => ReplaceOutcome::Replaced,
+          Err(e) => ReplaceOutcome::Failed(format!("rename failed: {e}")),
+        };
+        FileReplaceResult { path: search_result.path.clone(), outcome }
+      })
+      .collect()
   }
 
   async fn handle_confirm<Api: StoreApi<State, Action> + Send + Sync + 'static>(&self, store: Arc<Api>) {
@@ -64,15 +362,49 @@ impl ProcessReplaceThunk {
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
 
+    let transactional_replace = store.select(|state: &State| state.transactional_replace).await;
+    REPLACE_CANCELLED.store(false, Ordering::SeqCst);
+
     #[cfg(feature = "ast_grep")]
-    if search_text_state.kind == SearchTextKind::AstGrep {
-      self.process_ast_grep_replace(&store).await;
+    let mut results = if transactional_replace {
+      self.process_transactional_replace(&store).await
+    } else if search_text_state.kind == SearchTextKind::AstGrep {
+      self.process_ast_grep_replace(&store).await
     } else {
-      self.process_normal_replace(&store).await;
-    }
+      self.process_normal_replace(&store).await
+    };
 
     #[cfg(not(feature = "ast_grep"))]
-    self.process_normal_replace(&store).await;
+    let mut results = if transactional_replace {
+      self.process_transactional_replace(&store).await
+    } else {
+      self.process_normal_replace(&store).await
+    };
+
+    // Renames run last so the content replacements above still find the files at their original paths. They aren't
+    // part of a transactional replace: a failed rename is reported but doesn't undo the committed contents.
+    let is_rolled_back =
+      transactional_replace && results.iter().any(|r| matches!(r.outcome, ReplaceOutcome::Failed(_)));
+    let cancelled = ReplaceOutcome::Skipped(CANCELLED.to_string());
+    let is_cancelled = results.iter().any(|r| r.outcome == cancelled);
+    let rename_results = if is_rolled_back || is_cancelled {
+      let reason = if is_rolled_back { ROLLED_BACK } else { CANCELLED };
+      search_list
+        .list
+        .iter()
+        .filter(|r| r.kind == SearchResultKind::FilePath)
+        .map(|r| FileReplaceResult { path: r.path.clone(), outcome: ReplaceOutcome::Skipped(reason.to_string()) })
+        .collect()
+    } else {
+      self.process_file_path_renames(&store).await
+    };
+
+    let replaced_paths = |results: &[FileReplaceResult]| -> Vec<String> {
+      results.iter().filter(|r| r.outcome == ReplaceOutcome::Replaced).map(|r| r.path.clone()).collect()
+    };
+    let commits =
+      commit_replace(&store, &self.command_tx, &replaced_paths(&results), &replaced_paths(&rename_results)).await;
+    results.extend(rename_results);
 
     store.dispatch(Action::ResetState).await;
     let reset_action = AppAction::Tui(TuiAction::Reset);
@@ -80,10 +412,45 @@ impl ProcessReplaceThunk {
     let done_processing_status_action = AppAction::Tui(TuiAction::Status("".to_string()));
     self.command_tx.send(done_processing_status_action).unwrap();
 
-    let search_text_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Info(
-      "Search and replace completed successfully".to_string(),
-    )));
+    let unreplaced = results.iter().filter(|result| result.outcome != ReplaceOutcome::Replaced).count();
+    let mut message = if is_cancelled {
+      format!("Search and replace cancelled, {} of {} files were replaced", results.len() - unreplaced, results.len())
+    } else if unreplaced == 0 {
+      "Search and replace completed successfully".to_string()
+    } else {
+      format!("Search and replace completed, {unreplaced} of {} files were not replaced", results.len())
+    };
+    if !commits.is_empty() {
+      message.push_str(&format!(", committed {}", commits.join(", ")));
+    }
+    let search_text_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Info(message)));
     self.command_tx.send(search_text_action).unwrap();
+
+    if unreplaced > 0 {
+      for result in results.iter() {
+        match &result.outcome {
+          ReplaceOutcome::Replaced => {},
+          ReplaceOutcome::Skipped(reason) => log::warn!("Skipped {}: {}", result.path, reason),
+          ReplaceOutcome::Failed(reason) => log::error!("Failed to replace in {}: {}", result.path, reason),
+        }
+      }
+      let summary_dialog =
+        Action::SetDialog { dialog: Some(Dialog::ReplaceSummary(ReplaceSummaryDialogState { results, show: true })) };
+      store.dispatch(summary_dialog).await;
+    }
+  }
+
+  /// First root with results that is neither a git repository nor confirmed by the user yet.
+  fn next_unconfirmed_root(
+    project_roots: &[PathBuf],
+    confirmed_roots: &[PathBuf],
+    search_list: &SearchListState,
+  ) -> Option<PathBuf> {
+    project_roots
+      .iter()
+      .filter(|root| !confirmed_roots.contains(root) && !is_git_repo(root.to_path_buf()))
+      .find(|root| search_list.list.iter().any(|result| root_for_path(&result.path, project_roots) == **root))
+      .cloned()
   }
 
   async fn handle_cancel(&self, store: Arc<impl StoreApi<State, Action>>) {
@@ -92,25 +459,175 @@ impl ProcessReplaceThunk {
   }
 }
 
+/// Whether a replace in `paths` can go ahead. Files with uncommitted changes are listed in a dialog first, which
+/// dispatches `replace` again once the user chose. Clears the choice once it is used.
+pub async fn confirm_dirty_files<Api: StoreApi<State, Action> + Send + Sync + 'static>(
+  store: &Arc<Api>,
+  command_tx: &UnboundedSender<AppAction>,
+  paths: &[String],
+  replace: ThunkAction,
+) -> bool {
+  let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+  let dirty_files_choice = store.select(|state: &State| state.dirty_files_choice).await;
+
+  match dirty_files_choice {
+    Some(DirtyFilesChoice::Proceed) => {},
+    Some(DirtyFilesChoice::Stash) => {
+      let search_text = store.select(|state: &State| state.search_text.text.clone()).await;
+      let message = format!("serpl: before replacing {search_text}");
+      for (root, files) in dirty_files(paths, &project_roots) {
+        let relative_files: Vec<String> = files.iter().map(|file| relative_path(file, &root).to_string()).collect();
+        if let Err(e) = git::stash_files(&root, &relative_files, &message) {
+          log::error!("Failed to stash changes in {}: {}", root.display(), e);
+          let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(format!(
+            "Failed to stash changes in {}, nothing was replaced",
+            root.display()
+          ))));
+          command_tx.send(error_action).unwrap();
+          store.dispatch(Action::SetDirtyFilesChoice { choice: None }).await;
+          return false;
+        }
+      }
+    },
+    None => {
+      let files: Vec<String> = dirty_files(paths, &project_roots).into_iter().flat_map(|(_, files)| files).collect();
+      if !files.is_empty() {
+        let dirty_files_dialog = Action::SetDialog {
+          dialog: Some(Dialog::ConfirmDirtyFiles(DirtyFilesDialogState { files, replace, show: true })),
+        };
+        store.dispatch(dirty_files_dialog).await;
+        return false;
+      }
+    },
+  }
+
+  store.dispatch(Action::SetDirtyFilesChoice { choice: None }).await;
+  true
+}
+
+/// Result paths with uncommitted changes, per git repository root. A root git can't check is left out.
+fn dirty_files(paths: &[String], project_roots: &[PathBuf]) -> Vec<(PathBuf, Vec<String>)> {
+  let mut dirty_files = Vec::new();
+  for root in project_roots.iter().filter(|root| is_git_repo(root.to_path_buf())) {
+    let paths: Vec<&String> = paths.iter().filter(|path| root_for_path(path, project_roots) == *root).collect();
+    let relative_files: Vec<String> = paths.iter().map(|path| relative_path(path, root).to_string()).collect();
+
+    match git::dirty_files(root, &relative_files) {
+      Ok(dirty) if !dirty.is_empty() => {
+        let files = paths
+          .iter()
+          .filter(|path| dirty.iter().any(|file| file == relative_path(path, root)))
+          .map(|path| path.to_string())
+          .collect();
+        dirty_files.push((root.clone(), files));
+      },
+      Ok(_) => {},
+      Err(e) => log::error!("Failed to check {} for uncommitted changes: {}", root.display(), e),
+    }
+  }
+  dirty_files
+}
+
+/// Commits the replaced files and both paths of the renamed ones in each git repository when auto commit is on,
+/// returning the short hashes, followed by their root when there are several roots. Paths are the results' paths, so
+/// files a replace skipped or failed on keep their uncommitted changes out of the commit.
+pub async fn commit_replace(
+  store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  command_tx: &UnboundedSender<AppAction>,
+  replaced: &[String],
+  renamed: &[String],
+) -> Vec<String> {
+  let git_state = store.select(|state: &State| state.git.clone()).await;
+  if !git_state.auto_commit {
+    return Vec::new();
+  }
+  let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+  let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+  let ast_grep_state = store.select(|state: &State| state.ast_grep.clone()).await;
+  let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+
+  let message = commit_message(&search_text_state, &replace_text_state, &ast_grep_state);
+  let mut commits = Vec::new();
+  for root in project_roots.iter().filter(|root| is_git_repo(root.to_path_buf())) {
+    let in_root = |path: &&String| root_for_path(path, &project_roots) == *root;
+    let mut files: Vec<String> =
+      replaced.iter().filter(in_root).map(|path| relative_path(path, root).to_string()).collect();
+    for path in renamed.iter().filter(in_root) {
+      let path = relative_path(path, root).to_string();
+      let new_path = get_renamed_path(&path, &search_text_state, &replace_text_state);
+      if !new_path.is_empty() {
+        files.push(new_path);
+      }
+      files.push(path);
+    }
+    if files.is_empty() {
+      continue;
+    }
+
+    match git::commit_files(root, &files, &message, git_state.commit_branch.as_deref()) {
+      Ok(Some(hash)) if project_roots.len() == 1 => commits.push(hash),
+      Ok(Some(hash)) => commits.push(format!("{hash} in {}", root.display())),
+      Ok(None) => {},
+      Err(e) => {
+        log::error!("Failed to commit the replace in {}: {}", root.display(), e);
+        let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(format!(
+          "Failed to commit the replace in {}",
+          root.display()
+        ))));
+        command_tx.send(error_action).unwrap();
+      },
+    }
+  }
+  commits
+}
+
+fn commit_message(
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  ast_grep_state: &AstGrepState,
+) -> String {
+  let mut message = match ast_grep_state.rule_name() {
+    Some(rule_name) if search_text_state.text.is_empty() => format!("serpl: apply {rule_name}"),
+    _ => format!("serpl: replace '{}' with '{}'", search_text_state.text, replace_text_state.text),
+  };
+  message
+    .push_str(&format!("\n\nSearch mode: {:?}\nReplace mode: {:?}", search_text_state.kind, replace_text_state.kind));
+  if let Some(rule_file) = &ast_grep_state.rule_file {
+    message.push_str(&format!("\nRule: {}", rule_file.display()));
+  }
+  message
+}
+
 #[async_trait]
 impl<Api> Thunk<State, Action, Api> for ProcessReplaceThunk
 where
   Api: StoreApi<State, Action> + Send + Sync + 'static,
 {
   async fn execute(&self, store: Arc<Api>) {
-    let project_root = store.select(|state: &State| state.project_root.clone()).await;
+    if store.select(|state: &State| state.replace_progress.is_some()).await {
+      let error_action =
+        AppAction::Tui(TuiAction::Notify(NotificationEnum::Error("A replace is already running".to_string())));
+      self.command_tx.send(error_action).unwrap();
+      return;
+    }
+    // Results watch mode found gone have nothing left to replace.
+    store.dispatch(Action::DropRemovedResults).await;
+    let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+    let confirmed_roots = store.select(|state: &State| state.confirmed_roots.clone()).await;
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let force_replace = self.force_replace.0;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
-    if force_replace {
-      self.handle_confirm(store).await;
-    } else if search_text_state.text.is_empty() {
+    let has_search_query = store.select(|state: &State| state.has_search_query()).await;
+    let uses_ast_grep_rule = store.select(|state: &State| state.uses_ast_grep_rule()).await;
+    // Forcing only skips the empty replacement prompt, which the user already answered. Roots still need confirming.
+    if !has_search_query {
       let search_text_action =
         AppAction::Tui(TuiAction::Notify(NotificationEnum::Error("Search text cannot be empty".to_string())));
       self.command_tx.send(search_text_action).unwrap();
 
       return;
-    } else if replace_text_state.text.is_empty() {
+    } else if !force_replace && replace_text_state.text.is_empty() && !uses_ast_grep_rule {
       let confirm_dialog = Action::SetDialog {
         dialog: Some(Dialog::ConfirmReplace(ConfirmDialogState {
           message: "Replace text is empty, and replacing with an empty string will remove the matched text.\n Are you sure you want to continue?"
@@ -127,17 +644,21 @@ where
       store.dispatch(confirm_dialog).await;
 
       return;
-    } else if is_git_repo(project_root) {
-      self.handle_confirm(store.clone()).await;
-    } else {
+    } else if let Some(root) = Self::next_unconfirmed_root(&project_roots, &confirmed_roots, &search_list) {
+      // Roots outside of git are confirmed one at a time, skipping a root drops its results from this replace.
+      let is_single_root = project_roots.len() == 1;
+      let message = if is_single_root {
+        "This action will modify the files in this directory.\n Are you sure you want to continue?".to_string()
+      } else {
+        format!("This action will modify the files in {}.\n Are you sure you want to continue?", root.display())
+      };
       let confirm_dialog = Action::SetDialog {
         dialog: Some(Dialog::ConfirmGitDirectory(ConfirmDialogState {
-          message: "This action will modify the files in this directory.\n Are you sure you want to continue?"
-            .to_string(),
-          on_confirm: Some(DialogAction::ConfirmReplace),
-          on_cancel: Some(DialogAction::CancelReplace),
+          message,
+          on_confirm: Some(DialogAction::ConfirmRoot(root.clone())),
+          on_cancel: Some(if is_single_root { DialogAction::CancelReplace } else { DialogAction::SkipRoot(root) }),
           confirm_label: "Continue".to_string(),
-          cancel_label: "Cancel".to_string(),
+          cancel_label: if is_single_root { "Cancel" } else { "Skip" }.to_string(),
           show_cancel: true,
           show: true,
         })),
@@ -146,6 +667,92 @@ where
       store.dispatch(confirm_dialog).await;
 
       return;
+    } else if search_list.list.is_empty() {
+      self.handle_cancel(store).await;
+    } else {
+      let paths: Vec<String> = search_list.list.iter().map(|result| result.path.clone()).collect();
+      let replace = ThunkAction::ProcessReplace(ForceReplace(true));
+      if confirm_dirty_files(&store, &self.command_tx, &paths, replace).await {
+        self.handle_confirm(store).await;
+      }
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_replace_each_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let search_results: Vec<SearchResultState> = ["first.txt", "second.txt", "third.txt"]
+      .iter()
+      .map(|name| {
+        let path = dir.path().join(name);
+        fs::write(&path, "foo\n").unwrap();
+        SearchResultState { path: path.to_string_lossy().to_string(), ..Default::default() }
+      })
+      .collect();
+    let cancelled = AtomicBool::new(false);
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+
+    let mut run = ReplaceRun::new(search_results.len(), progress_tx, &cancelled);
+    let results = run.replace_each(&search_results, |_| {
+      // Esc pressed while the first file is replaced.
+      cancelled.store(true, Ordering::SeqCst);
+      Ok(b"bar\n".to_vec())
+    });
+
+    let outcomes: Vec<ReplaceOutcome> = results.into_iter().map(|result| result.outcome).collect();
+    let skipped = ReplaceOutcome::Skipped(CANCELLED.to_string());
+    assert_eq!(outcomes, vec![ReplaceOutcome::Replaced, skipped.clone(), skipped]);
+    assert_eq!(fs::read_to_string(dir.path().join("first.txt")).unwrap(), "bar\n");
+    assert_eq!(fs::read_to_string(dir.path().join("second.txt")).unwrap(), "foo\n");
+    assert_eq!(progress_rx.try_recv().unwrap(), ReplaceProgress { done: 1, total: 3, bytes: 4 });
+    assert!(progress_rx.try_recv().is_err());
+  }
+
+  #[test]
+  fn test_commit_contents_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    fs::write(path("written.txt"), "foo\n").unwrap();
+    let binary = ReplaceOutcome::Skipped("binary file".to_string());
+    let cancelled = AtomicBool::new(false);
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+
+    let mut run = ReplaceRun::new(2, progress_tx, &cancelled);
+    let contents = vec![(path("binary.bin"), Err(binary.clone())), (path("written.txt"), Ok(b"bar\n".to_vec()))];
+    let outcomes: Vec<ReplaceOutcome> =
+      commit_contents(contents, &mut run).into_iter().map(|result| result.outcome).collect();
+
+    assert_eq!(outcomes, vec![binary, ReplaceOutcome::Replaced]);
+    assert_eq!(fs::read_to_string(path("written.txt")).unwrap(), "bar\n");
+    assert_eq!(progress_rx.try_recv().unwrap(), ReplaceProgress { done: 1, total: 2, bytes: 0 });
+    assert_eq!(progress_rx.try_recv().unwrap(), ReplaceProgress { done: 2, total: 2, bytes: 4 });
+  }
+
+  #[test]
+  fn test_next_unconfirmed_root() {
+    let dir = tempfile::tempdir().unwrap();
+    let roots: Vec<PathBuf> =
+      ["repo", "first", "second", "unmatched"].iter().map(|name| dir.path().join(name)).collect();
+    roots.iter().for_each(|root| fs::create_dir_all(root).unwrap());
+    fs::create_dir(roots[0].join(".git")).unwrap();
+    let search_list = SearchListState {
+      list: roots[..3]
+        .iter()
+        .map(|root| SearchResultState { path: root.join("a.rs").to_string_lossy().to_string(), ..Default::default() })
+        .collect(),
+      ..Default::default()
+    };
+
+    let next = |confirmed: &[PathBuf]| ProcessReplaceThunk::next_unconfirmed_root(&roots, confirmed, &search_list);
+    assert_eq!(next(&[]), Some(roots[1].clone()));
+    assert_eq!(next(&roots[1..2]), Some(roots[2].clone()));
+    assert_eq!(next(&roots[1..3]), None);
+  }
+}
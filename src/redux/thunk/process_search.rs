@@ -3,249 +3,219 @@ use std::{
   fs,
   path::PathBuf,
   process::Command,
-  sync::Arc,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
 };
 
 use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
 use redux_rs::{
   middlewares::thunk::{self, Thunk},
   StoreApi,
 };
 use serde_json::from_str;
+use tokio::sync::mpsc::{self, UnboundedSender};
 
 use crate::{
+  action::{AppAction, TuiAction},
   astgrep::AstGrepOutput,
+  components::notifications::NotificationEnum,
+  git,
   redux::{
     action::Action,
-    state::{Match, Metadata, SearchListState, SearchResultState, SearchTextKind, SearchTextState, State, SubMatch},
+    state::{
+      Match, Metadata, ReplaceTextKind, SearchListState, SearchResultKind, SearchResultState, SearchTextKind,
+      SearchTextState, State, SubMatch,
+    },
+    utils::{relative_path, root_for_path, try_get_replace_regex},
   },
-  ripgrep::{RipgrepLines, RipgrepOutput, RipgrepSummary},
+  ripgrep::{self, RipgrepLines},
+  search_backend::{self, SearchBackend, SearchQuery},
 };
 
-pub struct ProcessSearchThunk {}
+/// Bumped by every search, so a slower earlier search stops overwriting the results of the current one.
+static SEARCH_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+pub struct ProcessSearchThunk {
+  command_tx: Arc<UnboundedSender<AppAction>>,
+  generation: u64,
+}
 
 impl ProcessSearchThunk {
-  pub fn new() -> Self {
-    Self {}
+  pub fn new(command_tx: Arc<UnboundedSender<AppAction>>) -> Self {
+    let generation = SEARCH_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    Self { command_tx, generation }
   }
 
-  fn get_context(lines: &[&str], start: usize, count: usize, forward: bool) -> Vec<String> {
-    let mut context = Vec::new();
-    let mut current = start;
+  /// The search paths narrowed to the git scope, None after reporting a failing git command.
+  async fn scoped_search_paths(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Option<Vec<PathBuf>> {
+    let search_paths = store.select(|state: &State| state.search_paths()).await;
+    let git_scope = store.select(|state: &State| state.git.scope.clone()).await;
+
+    match git::scope_files(&search_paths, &git_scope) {
+      Ok(Some(files)) => Some(files),
+      Ok(None) => Some(search_paths),
+      Err(e) => {
+        log::error!("Failed to list files with git: {}", e);
+        let error_action =
+          AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(format!("Failed to list files with git: {e}"))));
+        self.command_tx.send(error_action).unwrap();
+        None
+      },
+    }
+  }
 
-    for _ in 0..count {
-      if forward {
-        if current >= lines.len() {
-          break;
-        }
-        context.push(lines[current].to_string());
-        current += 1;
-      } else {
-        if current == 0 {
-          break;
-        }
-        current -= 1;
-        context.insert(0, lines[current].to_string());
+  async fn process_content_search(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    search_paths: Vec<PathBuf>,
+  ) {
+    let query = SearchQuery {
+      paths: search_paths,
+      search_text: store.select(|state: &State| state.search_text.clone()).await,
+      replace_text: store.select(|state: &State| state.replace_text.clone()).await,
+      ast_grep: store.select(|state: &State| state.ast_grep.clone()).await,
+      encoding: store.select(|state: &State| state.encoding).await,
+    };
+    let search_engine = store.select(|state: &State| state.search_engine.clone()).await;
+    let search_backends = store.select(|state: &State| state.search_backends.clone()).await;
+    let kind = query.search_text.kind;
+
+    // A configured engine that fails, e.g. a missing binary or a pattern it rejects, falls back to the built-in one
+    // for the mode.
+    let mut outcome = Err(eyre!("No search engine configured"));
+    if let Some(backend) = search_backend::configured_backend(&search_engine, &search_backends, kind) {
+      outcome = self.run_backend(store, backend, query.clone()).await;
+      if let Err(e) = &outcome {
+        log::warn!("Search with {} failed, falling back to the built-in engine: {}", search_engine, e);
       }
     }
+    if outcome.is_err() {
+      outcome = self.run_backend(store, search_backend::default_backend(kind), query).await;
+    }
 
-    context
+    if let Err(e) = outcome {
+      log::error!("Search failed: {}", e);
+      let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(e.to_string())));
+      self.command_tx.send(error_action).unwrap();
+    }
   }
 
-  async fn process_ast_grep_search(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
-    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
-    let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
-    let replace_text = replace_text_state.text.clone();
-    let project_root = store.select(|state: &State| state.project_root.clone()).await;
-
-    let mut args = vec!["run", "-p", &search_text_state.text, "--json=compact", project_root.to_str().unwrap()];
-    if !replace_text.is_empty() {
-      args.push("-r");
-      args.push(&replace_text);
+  /// Runs the backend off the async runtime, showing each file's results as they arrive. Stops updating the list
+  /// once a newer search started.
+  async fn run_backend(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    backend: Box<dyn SearchBackend>,
+    query: SearchQuery,
+  ) -> Result<()> {
+    let (results_tx, mut results_rx) = mpsc::unbounded_channel();
+    let search = tokio::task::spawn_blocking(move || backend.search(&query, &results_tx));
+
+    let mut search_list = SearchListState::default();
+    while let Some(result) = results_rx.recv().await {
+      search_list.list.push(result);
+      while let Ok(result) = results_rx.try_recv() {
+        search_list.list.push(result);
+      }
+      if self.is_superseded() {
+        return Ok(());
+      }
+      store.dispatch(Action::SetSearchList { search_list: search_list.clone() }).await;
     }
-    let output = Command::new("ast-grep").args(args).output().expect("Failed to execute ast-grep");
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    let ast_grep_results: Vec<AstGrepOutput> = from_str(&stdout).expect("Failed to parse ast-grep output");
-    let mut aggregated_results: HashMap<String, SearchResultState> = HashMap::new();
-    for result in ast_grep_results {
-      let file_content = fs::read_to_string(&result.file).unwrap_or_default();
-      let lines: Vec<&str> = file_content.lines().collect();
 
-      let context_before = Self::get_context(&lines, result.range.start.line, 3, false);
-      let context_after = Self::get_context(&lines, result.range.end.line, 3, true);
-
-      aggregated_results
-        .entry(result.file.clone())
-        .or_insert_with(|| SearchResultState {
-          index: None,
-          path: result.file.clone(),
-          matches: Vec::new(),
-          total_matches: 0,
-        })
-        .matches
-        .push(Match {
-          line_number: result.range.start.line,
-          lines: Some(RipgrepLines { text: result.lines }),
-          absolute_offset: result.range.byte_offset.start,
-          submatches: vec![SubMatch {
-            start: result.range.start.column,
-            end: result.range.end.column,
-            line_start: result.range.start.line,
-            line_end: result.range.end.line,
-          }],
-          replacement: result.replacement,
-          context_before,
-          context_after,
-        });
+    search_list.metadata = search.await??;
+    if self.is_superseded() {
+      return Ok(());
     }
-
-    let mut search_results: Vec<SearchResultState> = aggregated_results.into_values().collect();
-    for (index, result) in search_results.iter_mut().enumerate() {
+    search_list.list.sort_by(|a, b| a.path.cmp(&b.path));
+    for (index, result) in search_list.list.iter_mut().enumerate() {
       result.index = Some(index);
-      result.total_matches = result.matches.len();
     }
-
-    let search_list_state = SearchListState {
-      list: search_results.clone(),
-      metadata: Metadata {
-        elapsed_time: 0,
-        matched_lines: search_results.iter().map(|r| r.total_matches).sum(),
-        matches: search_results.iter().map(|r| r.total_matches).sum(),
-        searches: 1,
-        searches_with_match: if search_results.is_empty() { 0 } else { 1 },
-      },
-    };
-
-    store.dispatch(Action::SetSearchList { search_list: search_list_state }).await;
+    store.dispatch(Action::SetSearchList { search_list }).await;
+    Ok(())
   }
 
-  async fn process_normal_search(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
-    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
-    let project_root = store.select(|state: &State| state.project_root.clone()).await;
-    let mut rg_args = vec!["--json", "-C", "3"];
+  fn is_superseded(&self) -> bool {
+    SEARCH_GENERATION.load(Ordering::SeqCst) != self.generation
+  }
 
-    match search_text_state.kind {
-      SearchTextKind::Regex => rg_args.push(&search_text_state.text),
-      SearchTextKind::MatchCase => rg_args.extend(&["-s", &search_text_state.text]),
-      SearchTextKind::MatchWholeWord => rg_args.extend(&["-w", "-i", &search_text_state.text]),
-      SearchTextKind::MatchCaseWholeWord => rg_args.extend(&["-w", "-s", &search_text_state.text]),
-      SearchTextKind::Simple => rg_args.extend(&["-i", "-F", &search_text_state.text]),
-      #[cfg(feature = "ast_grep")]
-      SearchTextKind::AstGrep => {},
+  /// Orders results by the project root they belong to, since ripgrep walks several roots in parallel.
+  async fn group_by_project_root(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+    let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+    if project_roots.len() < 2 {
+      return;
     }
 
-    let project_root_str = project_root.to_string_lossy();
-    rg_args.push(&project_root_str);
-
-    let output = Command::new("rg").args(&rg_args).output().expect("Failed to execute ripgrep");
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    let mut results = Vec::new();
-    let mut path_to_result: HashMap<String, usize> = HashMap::new();
-    let mut summary: Option<RipgrepSummary> = None;
-
-    let mut context_buffer: VecDeque<(usize, String)> = VecDeque::new();
-
-    for line in stdout.lines() {
-      if let Ok(rg_output) = serde_json::from_str::<RipgrepOutput>(line) {
-        match rg_output.kind.as_str() {
-          "match" | "context" => {
-            if let Some(data) = rg_output.data {
-              let path = data.path.unwrap().text;
-              let line_number = data.line_number.unwrap_or_default() as usize;
-              let absolute_offset = data.absolute_offset.unwrap_or_default();
-
-              let search_result_index = path_to_result.entry(path.clone()).or_insert_with(|| {
-                let index = results.len();
-                results.push(SearchResultState {
-                  index: Some(index),
-                  path: path.clone(),
-                  matches: Vec::new(),
-                  total_matches: 0,
-                });
-                index
-              });
-
-              let result = &mut results[*search_result_index];
-
-              if rg_output.kind == "match" {
-                let submatches: Vec<SubMatch> = data
-                  .submatches
-                  .unwrap_or_default()
-                  .into_iter()
-                  .map(|sm| SubMatch { start: sm.start as usize, end: sm.end as usize, line_start: 0, line_end: 0 })
-                  .collect();
-
-                let mut context_before: Vec<String> = context_buffer.drain(..).map(|(_, line)| line).collect();
-                if context_before.len() > 3 {
-                  context_before = context_before.clone().into_iter().skip(context_before.len() - 3).collect();
-                }
-
-                result.matches.push(Match {
-                  lines: data.lines.clone(),
-                  line_number,
-                  context_before,
-                  context_after: Vec::new(),
-                  absolute_offset: absolute_offset as usize,
-                  submatches: submatches.clone(),
-                  replacement: None,
-                });
-                result.total_matches += submatches.len();
-
-                context_buffer.push_back((line_number, data.lines.unwrap().text));
-              } else {
-                context_buffer.push_back((line_number, data.lines.clone().unwrap().text));
-                if context_buffer.len() > 4 {
-                  context_buffer.pop_front();
-                }
+    let mut search_list = store.select(|state: &State| state.search_result.clone()).await;
+    search_list.list.sort_by_key(|result| {
+      let root = root_for_path(&result.path, &project_roots);
+      project_roots.iter().position(|r| *r == root)
+    });
+    for (index, result) in search_list.list.iter_mut().enumerate() {
+      result.index = Some(index);
+    }
+    store.dispatch(Action::SetSearchList { search_list }).await;
+  }
 
-                if let Some(last_match) = result.matches.last_mut() {
-                  if line_number > last_match.line_number && last_match.context_after.len() < 3 {
-                    last_match.context_after.push(data.lines.unwrap().text);
-                  }
-                }
-              }
-            }
-          },
-          "summary" => {
-            if let Some(data) = rg_output.data {
-              summary = Some(RipgrepSummary {
-                elapsed_time: data.elapsed_total.unwrap().nanos,
-                matched_lines: data.stats.as_ref().unwrap().matched_lines,
-                matches: data.stats.as_ref().unwrap().matches,
-                searches: data.stats.as_ref().unwrap().searches,
-                searches_with_match: data.stats.as_ref().unwrap().searches_with_match,
-              });
-            }
-          },
-          _ => {},
-        }
-      }
+  /// Selects the result of a file that was selected by path only, as a resumed session does, once the search found
+  /// it again.
+  async fn restore_selection(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+    let selected_result = store.select(|state: &State| state.selected_result.clone()).await;
+    if selected_result.index.is_some() || selected_result.path.is_empty() || self.is_superseded() {
+      return;
+    }
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
+    if let Some(result) = search_list.list.iter().find(|result| result.path == selected_result.path) {
+      store.dispatch(Action::SetSelectedResult { result: result.clone() }).await;
     }
+  }
 
-    let metadata = if let Some(s) = summary {
-      Metadata {
-        elapsed_time: s.elapsed_time,
-        matched_lines: s.matched_lines,
-        matches: s.matches,
-        searches: s.searches,
-        searches_with_match: s.searches_with_match,
-      }
-    } else {
-      Metadata::default()
-    };
+  async fn process_file_path_search(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    search_paths: &[PathBuf],
+  ) {
+    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+    let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+    let mut search_list = store.select(|state: &State| state.search_result.clone()).await;
 
-    let search_list_state = SearchListState { list: results, metadata };
+    let Ok(re) = try_get_replace_regex(&search_text_state, &replace_text_state) else {
+      return;
+    };
+    for path in ripgrep::native::list_files(search_paths) {
+      let project_root = root_for_path(&path, &project_roots);
+      let relative = relative_path(&path, &project_root);
+      let submatches: Vec<SubMatch> = re
+        .find_iter(relative)
+        .map(|m| SubMatch { start: m.start(), end: m.end(), line_start: 0, line_end: 0 })
+        .collect();
+      if submatches.is_empty() {
+        continue;
+      }
 
-    store.dispatch(Action::SetSearchList { search_list: search_list_state }).await;
-  }
-}
+      search_list.list.push(SearchResultState {
+        index: Some(search_list.list.len()),
+        path: path.clone(),
+        total_matches: submatches.len(),
+        matches: vec![Match {
+          lines: Some(RipgrepLines { text: relative.to_string() }),
+          submatches,
+          ..Default::default()
+        }],
+        kind: SearchResultKind::FilePath,
+        content_hash: None,
+        removed: false,
+      });
+    }
 
-impl Default for ProcessSearchThunk {
-  fn default() -> Self {
-    Self::new()
+    store.dispatch(Action::SetSearchList { search_list }).await;
   }
 }
 
@@ -256,18 +226,40 @@ where
 {
   async fn execute(&self, store: Arc<Api>) {
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+    let has_search_query = store.select(|state: &State| state.has_search_query()).await;
+    let has_search_paths = store.select(|state: &State| !state.search_paths().is_empty()).await;
 
-    if !search_text_state.text.is_empty() {
+    if has_search_query && has_search_paths {
       store.dispatch(Action::SetSearchList { search_list: SearchListState::default() }).await;
 
+      // An empty scope searches nothing, handing the backends no paths would search the working directory instead.
+      let Some(search_paths) = self.scoped_search_paths(&store).await else {
+        return;
+      };
+      if search_paths.is_empty() {
+        return;
+      }
+
+      self.process_content_search(&store, search_paths.clone()).await;
+      if self.is_superseded() {
+        return;
+      }
+
       #[cfg(feature = "ast_grep")]
       if search_text_state.kind == SearchTextKind::AstGrep {
-        self.process_ast_grep_search(&store).await;
-      } else {
-        self.process_normal_search(&store).await;
+        self.group_by_project_root(&store).await;
+        self.restore_selection(&store).await;
+        return;
       }
-      #[cfg(not(feature = "ast_grep"))]
-      self.process_normal_search(&store).await;
+
+      let include_file_paths = store.select(|state: &State| state.include_file_paths).await;
+      let replace_text_kind = store.select(|state: &State| state.replace_text.kind).await;
+      if include_file_paths && replace_text_kind != ReplaceTextKind::DeleteLine {
+        self.process_file_path_search(&store, &search_paths).await;
+      }
+
+      self.group_by_project_root(&store).await;
+      self.restore_selection(&store).await;
     }
   }
 }
@@ -1,7 +1,7 @@
 use std::{collections::HashSet, fs, io::Write, path::PathBuf, process::Command, sync::Arc, time::Duration};
 
 use async_trait::async_trait;
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result};
 use ratatui::style::Color;
 use redux_rs::{
   middlewares::thunk::{self, Thunk},
@@ -17,9 +17,12 @@ use crate::{
   components::notifications::NotificationEnum,
   redux::{
     action::Action,
-    state::{ConfirmDialogState, Dialog, DialogAction, ReplaceTextKind, SearchTextKind, State},
-    thunk::{ForceReplace, ThunkAction},
-    utils::{replace_file_ast, replace_file_normal},
+    state::{ConfirmDialogState, Dialog, DialogAction, ReplaceTextKind, SearchResultKind, SearchTextKind, State},
+    thunk::{
+      process_replace::{commit_replace, confirm_dirty_files},
+      ForceReplace, ThunkAction,
+    },
+    utils::{rename_file_path, replace_file_ast, replace_file_normal, root_for_path},
   },
   utils::is_git_repo,
 };
@@ -34,39 +37,54 @@ impl ProcessSingleFileReplaceThunk {
     Self { command_tx, file_index }
   }
 
-  async fn process_ast_grep_replace(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+  async fn process_ast_grep_replace(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Result<()> {
     let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let ast_grep_state = store.select(|state: &State| state.ast_grep.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
 
     if let Some(search_result) = search_list.list.get(self.file_index) {
-      replace_file_ast(search_result, &search_text_state, &replace_text_state);
+      replace_file_ast(search_result, &search_text_state, &replace_text_state, &ast_grep_state, encoding)?;
     }
+    Ok(())
   }
 
-  async fn process_normal_replace(&self, store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>) {
+  async fn process_file_path_rename(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Result<()> {
     let search_list = store.select(|state: &State| state.search_result.clone()).await;
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let project_roots = store.select(|state: &State| state.project_roots.clone()).await;
+
+    if let Some(search_result) = search_list.list.get(self.file_index) {
+      let project_root = root_for_path(&search_result.path, &project_roots);
+      rename_file_path(search_result, &search_text_state, &replace_text_state, &project_root)
+        .map_err(|e| eyre!("Failed to rename {}: {e}", search_result.path))?;
+    }
+    Ok(())
+  }
+
+  async fn process_normal_replace(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+  ) -> Result<()> {
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
+    let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
+    let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let encoding = store.select(|state: &State| state.encoding).await;
 
     if let Some(search_result) = search_list.list.get(self.file_index) {
       let file_path = &search_result.path;
-      let content = fs::read_to_string(file_path).expect("Unable to read file");
-      let mut lines: Vec<String> = content.lines().map(String::from).collect();
-
-      if replace_text_state.kind == ReplaceTextKind::DeleteLine {
-        let matched_lines: Vec<usize> = search_result.matches.iter().map(|m| m.line_number - 1).collect();
-        for &line_index in matched_lines.iter().rev() {
-          if line_index < lines.len() {
-            lines.remove(line_index);
-          }
-        }
-        let new_content = lines.join("\n");
-        fs::write(file_path, new_content).expect("Unable to write file");
-      } else {
-        replace_file_normal(search_result, &search_text_state, &replace_text_state);
-      }
+      let result = replace_file_normal(search_result, &search_text_state, &replace_text_state, encoding);
+      result.map_err(|e| eyre!("Failed to replace in {file_path}: {e}"))?;
     }
+    Ok(())
   }
 }
 
@@ -76,26 +94,62 @@ where
   Api: StoreApi<State, Action> + Send + Sync + 'static,
 {
   async fn execute(&self, store: Arc<Api>) {
+    if store.select(|state: &State| state.replace_progress.is_some()).await {
+      let error_action =
+        AppAction::Tui(TuiAction::Notify(NotificationEnum::Error("A replace is already running".to_string())));
+      self.command_tx.send(error_action).unwrap();
+      return;
+    }
     let search_text_state = store.select(|state: &State| state.search_text.clone()).await;
     let replace_text_state = store.select(|state: &State| state.replace_text.clone()).await;
+    let file_index = self.file_index;
+    let result = store.select(move |state: &State| state.search_result.list.get(file_index).cloned()).await;
+    let result_kind = result.as_ref().map(|r| r.kind);
+    let path = result.map(|r| r.path).unwrap_or_default();
+
+    let replace = ThunkAction::ProcessSingleFileReplace(file_index);
+    if !confirm_dirty_files(&store, &self.command_tx, std::slice::from_ref(&path), replace).await {
+      return;
+    }
 
-    #[cfg(feature = "ast_grep")]
-    if search_text_state.kind == SearchTextKind::AstGrep {
-      self.process_ast_grep_replace(&store).await;
+    let result = if result_kind == Some(SearchResultKind::FilePath) {
+      self.process_file_path_rename(&store).await
     } else {
-      self.process_normal_replace(&store).await;
+      #[cfg(feature = "ast_grep")]
+      let result = if search_text_state.kind == SearchTextKind::AstGrep {
+        self.process_ast_grep_replace(&store).await
+      } else {
+        self.process_normal_replace(&store).await
+      };
+
+      #[cfg(not(feature = "ast_grep"))]
+      let result = self.process_normal_replace(&store).await;
+
+      result
+    };
+
+    // A failed file stays in the list, so the user can look into it and retry.
+    if let Err(e) = result {
+      log::error!("{}", e);
+      let error_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Error(e.to_string())));
+      self.command_tx.send(error_action).unwrap();
+      return;
     }
 
-    #[cfg(not(feature = "ast_grep"))]
-    self.process_normal_replace(&store).await;
+    let (replaced, renamed) =
+      if result_kind == Some(SearchResultKind::FilePath) { (vec![], vec![path]) } else { (vec![path], vec![]) };
+    let commits = commit_replace(&store, &self.command_tx, &replaced, &renamed).await;
 
     store.dispatch(Action::RemoveFileFromList { index: self.file_index }).await;
 
     let done_processing_status_action = AppAction::Tui(TuiAction::Status("".to_string()));
     self.command_tx.send(done_processing_status_action).unwrap();
 
-    let notification_action =
-      AppAction::Tui(TuiAction::Notify(NotificationEnum::Info("File replacement completed successfully".to_string())));
+    let mut message = "File replacement completed successfully".to_string();
+    if !commits.is_empty() {
+      message.push_str(&format!(", committed {}", commits.join(", ")));
+    }
+    let notification_action = AppAction::Tui(TuiAction::Notify(NotificationEnum::Info(message)));
     self.command_tx.send(notification_action).unwrap();
   }
 }
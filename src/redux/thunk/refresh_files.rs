@@ -0,0 +1,124 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use redux_rs::{middlewares::thunk::Thunk, StoreApi};
+use tokio::sync::mpsc;
+
+use crate::{
+  redux::{
+    action::Action,
+    state::{GitScope, SearchListState, SearchResultState, State},
+    utils::{is_under, merge_refreshed_results, same_path},
+  },
+  search_backend::{self, SearchBackend, SearchQuery},
+};
+
+/// Searches the files watch mode saw change again and merges their results into the current list.
+pub struct RefreshFilesThunk {
+  paths: Vec<PathBuf>,
+}
+
+impl RefreshFilesThunk {
+  pub fn new(paths: Vec<PathBuf>) -> Self {
+    Self { paths }
+  }
+
+  /// Of the changed files, those the current search covers. Files outside the git scope are only refreshed while
+  /// listed, since the scope's files were listed when the search ran.
+  fn searched_paths(&self, search_paths: &[PathBuf], search_list: &SearchListState, scope: &GitScope) -> Vec<PathBuf> {
+    let is_listed = |path: &Path| search_list.list.iter().any(|result| same_path(Path::new(&result.path), path));
+    self
+      .paths
+      .iter()
+      .filter(|path| search_paths.iter().any(|search_path| is_under(path, search_path)))
+      .filter(|path| *scope == GitScope::All || is_listed(path))
+      .cloned()
+      .collect()
+  }
+
+  /// Searches the files still on disk with the backend of a full search, falling back to the built-in one the same
+  /// way.
+  async fn search(
+    &self,
+    store: &Arc<impl StoreApi<State, Action> + Send + Sync + 'static>,
+    paths: Vec<PathBuf>,
+  ) -> Result<Vec<SearchResultState>> {
+    let query = SearchQuery {
+      paths: paths.into_iter().filter(|path| path.is_file()).collect(),
+      search_text: store.select(|state: &State| state.search_text.clone()).await,
+      replace_text: store.select(|state: &State| state.replace_text.clone()).await,
+      ast_grep: store.select(|state: &State| state.ast_grep.clone()).await,
+      encoding: store.select(|state: &State| state.encoding).await,
+    };
+    if query.paths.is_empty() {
+      return Ok(Vec::new());
+    }
+    let search_engine = store.select(|state: &State| state.search_engine.clone()).await;
+    let search_backends = store.select(|state: &State| state.search_backends.clone()).await;
+    let kind = query.search_text.kind;
+
+    if let Some(backend) = search_backend::configured_backend(&search_engine, &search_backends, kind) {
+      match Self::run_backend(backend, query.clone()).await {
+        Ok(results) => return Ok(results),
+        Err(e) => log::warn!("Search with {} failed, falling back to the built-in engine: {}", search_engine, e),
+      }
+    }
+    Self::run_backend(search_backend::default_backend(kind), query).await
+  }
+
+  async fn run_backend(backend: Box<dyn SearchBackend>, query: SearchQuery) -> Result<Vec<SearchResultState>> {
+    let (results_tx, mut results_rx) = mpsc::unbounded_channel();
+    tokio::task::spawn_blocking(move || backend.search(&query, &results_tx)).await??;
+
+    let mut results = Vec::new();
+    while let Ok(result) = results_rx.try_recv() {
+      results.push(result);
+    }
+    Ok(results)
+  }
+}
+
+#[async_trait]
+impl<Api> Thunk<State, Action, Api> for RefreshFilesThunk
+where
+  Api: StoreApi<State, Action> + Send + Sync + 'static,
+{
+  async fn execute(&self, store: Arc<Api>) {
+    let has_search_query = store.select(|state: &State| state.has_search_query()).await;
+    let is_replacing = store.select(|state: &State| state.replace_progress.is_some()).await;
+    if !has_search_query || is_replacing {
+      return;
+    }
+
+    let search_paths = store.select(|state: &State| state.search_paths()).await;
+    let search_list = store.select(|state: &State| state.search_result.clone()).await;
+    let scope = store.select(|state: &State| state.git.scope.clone()).await;
+    let paths = self.searched_paths(&search_paths, &search_list, &scope);
+    if paths.is_empty() {
+      return;
+    }
+
+    let fresh = match self.search(&store, paths.clone()).await {
+      Ok(fresh) => fresh,
+      Err(e) => {
+        log::error!("Failed to refresh changed files: {}", e);
+        return;
+      },
+    };
+
+    // The list may have changed while searching, the merge applies to the current one.
+    let mut search_list = store.select(|state: &State| state.search_result.clone()).await;
+    merge_refreshed_results(&mut search_list.list, &paths, fresh);
+    let selected_result = store.select(|state: &State| state.selected_result.clone()).await;
+    let refreshed_selection = selected_result.index.and_then(|index| search_list.list.get(index)).cloned();
+    store.dispatch(Action::SetSearchList { search_list }).await;
+
+    if let Some(result) = refreshed_selection.filter(|result| result.path == selected_result.path) {
+      store.dispatch(Action::SetSelectedResult { result }).await;
+    }
+  }
+}
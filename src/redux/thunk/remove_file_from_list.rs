@@ -40,11 +40,12 @@ where
     if self.index < search_list.list.len() {
       // Remove the file from the list in the state
       let mut updated_list = search_list.list.clone();
-      updated_list.remove(self.index);
+      let removed_result = updated_list.remove(self.index);
 
       // Update the state with the new list
       let updated_search_list = SearchListState { list: updated_list.clone(), ..search_list };
       store.dispatch(Action::SetSearchList { search_list: updated_search_list }).await;
+      store.dispatch(Action::ExcludeFile { path: removed_result.path }).await;
 
       // Update the selected result to None or the next available item
       let new_selected_result = if updated_list.is_empty() {
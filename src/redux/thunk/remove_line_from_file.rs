@@ -35,12 +35,15 @@ where
       if self.line_index < file_result.matches.len() {
         let deleted_match = file_result.matches.remove(self.line_index);
         file_result.total_matches -= deleted_match.submatches.len();
+        let excluded_line =
+          Action::ExcludeLine { path: file_result.path.clone(), line_number: deleted_match.line_number };
 
         if file_result.matches.is_empty() {
           search_list.list.remove(self.file_index);
         }
 
         store.dispatch(Action::SetSearchList { search_list: search_list.clone() }).await;
+        store.dispatch(excluded_line).await;
 
         if !search_list.list.is_empty() {
           let new_selected_index = self.file_index.min(search_list.list.len() - 1);
@@ -1,55 +1,358 @@
-use std::fs;
+use std::{
+  collections::{HashMap, HashSet},
+  fs,
+  hash::{DefaultHasher, Hash, Hasher},
+  io,
+  path::{Path, PathBuf},
+  process::Command,
+};
 
+use color_eyre::eyre::{eyre, Result};
+use encoding_rs::{Encoding, UTF_8};
 use regex::RegexBuilder;
 use serde_json::from_str;
 
 use crate::{
-  astgrep::AstGrepOutput,
-  redux::state::{ReplaceTextKind, SearchTextKind},
+  astgrep::{AstGrepOutput, ReplacementOffsets},
+  encoding::{self, FileEncoding},
+  redux::state::{
+    AstGrepState, FileReplaceResult, Match, ReplaceOutcome, ReplaceTextKind, ReplaceTextState, SearchResultKind,
+    SearchResultState, SearchTextKind, SearchTextState,
+  },
+  utils::is_git_repo,
 };
 
+/// Arguments for an ast-grep invocation: a `scan` with the loaded rule file, whose `fix` provides the replacement,
+/// otherwise a `run` of the typed pattern in the selected language.
+pub fn ast_grep_args(pattern: &str, replacement: Option<&str>, ast_grep_state: &AstGrepState) -> Vec<String> {
+  let mut args = Vec::new();
+  if let Some(rule_file) = &ast_grep_state.rule_file {
+    let is_project_config = rule_file.file_stem().is_some_and(|stem| stem == "sgconfig");
+    args.push("scan".to_string());
+    args.push(if is_project_config { "-c" } else { "-r" }.to_string());
+    args.push(rule_file.to_string_lossy().to_string());
+  } else {
+    args.extend(["run".to_string(), "-p".to_string(), pattern.to_string()]);
+    if let Some(lang) = &ast_grep_state.lang {
+      args.extend(["--lang".to_string(), lang.clone()]);
+    }
+    if let Some(replacement) = replacement {
+      args.extend(["-r".to_string(), replacement.to_string()]);
+    }
+  }
+  args.push("--json=compact".to_string());
+  args
+}
+
+/// Runs a structural search over `paths`. Patterns are matched in process with the `ast_grep_native` feature, rule
+/// files and builds without it go through the ast-grep binary.
+pub fn run_ast_grep(
+  paths: &[PathBuf],
+  pattern: &str,
+  replacement: Option<&str>,
+  ast_grep_state: &AstGrepState,
+) -> Result<Vec<AstGrepOutput>> {
+  #[cfg(feature = "ast_grep_native")]
+  if ast_grep_state.rule_file.is_none() {
+    return Ok(crate::astgrep::native::search(paths, pattern, replacement, ast_grep_state.lang.as_deref())?);
+  }
+
+  let output = Command::new("ast-grep")
+    .args(ast_grep_args(pattern, replacement, ast_grep_state))
+    .args(paths)
+    .output()
+    .map_err(|e| eyre!("Failed to execute ast-grep: {e}"))?;
+
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  if stdout.trim().is_empty() && !output.status.success() {
+    return Err(eyre!("ast-grep failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+  }
+  from_str(&stdout).map_err(|e| eyre!("Failed to parse ast-grep output: {e}"))
+}
+
+/// Files handed to a single ast-grep invocation, keeping the command line within system limits.
+pub const AST_GREP_BATCH_SIZE: usize = 500;
+
+/// A replacement of a byte range in a file's content.
+pub struct AstEdit {
+  pub range: std::ops::Range<usize>,
+  pub text: String,
+}
+
 pub fn replace_file_ast(
   search_result: &crate::redux::state::SearchResultState,
   search_text_state: &crate::redux::state::SearchTextState,
   replace_text_state: &crate::redux::state::ReplaceTextState,
-) {
-  let file_path = &search_result.path;
+  ast_grep_state: &AstGrepState,
+  encoding: Option<&'static Encoding>,
+) -> Result<()> {
+  if replace_file_ast_from_search(search_result, encoding)? {
+    return Ok(());
+  }
+  let results = replace_files_ast(&[search_result], search_text_state, replace_text_state, ast_grep_state, encoding);
+  match results.into_iter().next().map(|result| result.outcome) {
+    Some(ReplaceOutcome::Skipped(reason) | ReplaceOutcome::Failed(reason)) => {
+      Err(eyre!("Failed to replace in {}: {reason}", search_result.path))
+    },
+    _ => Ok(()),
+  }
+}
 
-  let mut content = fs::read_to_string(file_path).expect("Unable to read file");
-  let lines: Vec<&str> = content.lines().collect();
+/// Applies the replacements ast-grep reported during the search. Returns false without touching the file when it
+/// changed since the search or a match has no replacement, in which case ast-grep has to run again.
+pub fn replace_file_ast_from_search(
+  search_result: &SearchResultState,
+  encoding: Option<&'static Encoding>,
+) -> io::Result<bool> {
+  let Some(content) = replaced_content_ast_from_search(search_result, encoding)? else {
+    return Ok(false);
+  };
+  fs::write(&search_result.path, content)?;
+  Ok(true)
+}
 
-  let lines_to_replace: std::collections::HashSet<usize> =
-    search_result.matches.iter().map(|m| m.line_number).collect();
+/// New bytes of a file from the replacements ast-grep reported during the search, in the file's own encoding. None
+/// when they no longer apply.
+pub fn replaced_content_ast_from_search(
+  search_result: &SearchResultState,
+  encoding: Option<&'static Encoding>,
+) -> io::Result<Option<Vec<u8>>> {
+  let (mut content, file_encoding) = read_file_ast(&search_result.path, encoding)?;
+  let matches: Vec<&Match> = search_result.matches.iter().collect();
+  let Some(edits) = ast_edits_from_search(search_result, &content, &matches) else {
+    return Ok(None);
+  };
 
-  let output = std::process::Command::new("ast-grep")
-    .args(["run", "-p", &search_text_state.text, "-r", &replace_text_state.text, "--json=compact", file_path])
-    .output()
-    .expect("Failed to execute ast-grep for replacement");
+  apply_ast_edits(&mut content, edits);
+  encoding::encode(&content, file_encoding).map(Some)
+}
 
-  let stdout = String::from_utf8_lossy(&output.stdout);
-  let ast_grep_results: Vec<AstGrepOutput> = from_str(&stdout).expect("Failed to parse ast-grep output");
-
-  for result in ast_grep_results.iter().rev() {
-    if let (Some(replacement), Some(offsets)) = (&result.replacement, &result.replacement_offsets) {
-      if lines_to_replace.contains(&result.range.start.line) {
-        let start = offsets.start;
-        let end = offsets.end;
-        content.replace_range(start..end, replacement);
+/// Reads a file to replace ast-grep matches in. ast-grep counts byte offsets in the file as it is on disk, so a UTF-8
+/// byte order mark stays in the text, and is left out of the encoding to not be written twice.
+fn read_file_ast(path: &str, encoding: Option<&'static Encoding>) -> io::Result<(String, FileEncoding)> {
+  let (mut content, mut file_encoding) = encoding::read_file(path, encoding)?;
+  if file_encoding.bom && file_encoding.encoding == UTF_8 {
+    content.insert(0, '\u{feff}');
+    file_encoding.bom = false;
+  }
+  Ok((content, file_encoding))
+}
+
+/// Replaces the matches of several files, running ast-grep once per batch of files instead of once per file. A failed
+/// ast-grep run fails its batch, the other batches still run.
+pub fn replace_files_ast(
+  search_results: &[&SearchResultState],
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  ast_grep_state: &AstGrepState,
+  encoding: Option<&'static Encoding>,
+) -> Vec<FileReplaceResult> {
+  replaced_contents_ast(search_results, search_text_state, replace_text_state, ast_grep_state, encoding)
+    .into_iter()
+    .map(|(path, content)| {
+      match content {
+        Ok(content) => {
+          let result = fs::write(&path, content);
+          file_replace_result(&path, result)
+        },
+        Err(outcome) => FileReplaceResult { path, outcome },
       }
+    })
+    .collect()
+}
+
+/// New bytes of several files from batched ast-grep runs, in each file's own encoding, or why a file can't be
+/// replaced.
+pub fn replaced_contents_ast(
+  search_results: &[&SearchResultState],
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  ast_grep_state: &AstGrepState,
+  encoding: Option<&'static Encoding>,
+) -> Vec<(String, Result<Vec<u8>, ReplaceOutcome>)> {
+  let mut contents = Vec::new();
+  for batch in search_results.chunks(AST_GREP_BATCH_SIZE) {
+    let paths: Vec<PathBuf> = batch.iter().map(|search_result| PathBuf::from(&search_result.path)).collect();
+    let ast_grep_results =
+      match run_ast_grep(&paths, &search_text_state.text, Some(&replace_text_state.text), ast_grep_state) {
+        Ok(ast_grep_results) => ast_grep_results,
+        Err(e) => {
+          contents.extend(
+            batch.iter().map(|search_result| (search_result.path.clone(), Err(ReplaceOutcome::Failed(e.to_string())))),
+          );
+          continue;
+        },
+      };
+
+    let mut results_by_file: HashMap<PathBuf, Vec<AstGrepOutput>> = HashMap::new();
+    for result in ast_grep_results {
+      results_by_file.entry(canonical_path(&result.file)).or_default().push(result);
+    }
+
+    for search_result in batch {
+      let Some(file_results) = results_by_file.get(&canonical_path(&search_result.path)) else {
+        let outcome = ReplaceOutcome::Skipped("no longer matches".to_string());
+        contents.push((search_result.path.clone(), Err(outcome)));
+        continue;
+      };
+      let matches: Vec<&Match> = search_result.matches.iter().collect();
+      let content = read_file_ast(&search_result.path, encoding)
+        .and_then(|(mut content, file_encoding)| {
+          apply_ast_edits(&mut content, ast_edits_from_results(file_results, &matches));
+          encoding::encode(&content, file_encoding)
+        })
+        .map_err(|e| file_replace_result(&search_result.path, Err(e)).outcome);
+      contents.push((search_result.path.clone(), content));
     }
   }
 
-  fs::write(file_path, content).expect("Unable to write file");
+  contents
+}
+
+/// Outcome of replacing in a file from the result of reading and writing it. Files that are gone, binary or can't be
+/// decoded are skipped rather than failed, serpl never changed them.
+pub fn file_replace_result(path: &str, result: io::Result<()>) -> FileReplaceResult {
+  let outcome = match result {
+    Ok(()) => ReplaceOutcome::Replaced,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => ReplaceOutcome::Skipped("file no longer exists".to_string()),
+    Err(e) if e.kind() == io::ErrorKind::InvalidData => ReplaceOutcome::Skipped(e.to_string()),
+    Err(e) => ReplaceOutcome::Failed(e.to_string()),
+  };
+  FileReplaceResult { path: path.to_string(), outcome }
+}
+
+/// Replaces the single match at `match_index`, then shifts the file's remaining matches past the edit so they stay
+/// addressable by byte range. Fails when the file changed and ast-grep no longer finds the match where it was.
+pub fn replace_match_ast(
+  search_result: &mut SearchResultState,
+  match_index: usize,
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  ast_grep_state: &AstGrepState,
+  encoding: Option<&'static Encoding>,
+) -> Result<()> {
+  let Some(selected) = search_result.matches.get(match_index) else {
+    return Ok(());
+  };
+  let (mut content, file_encoding) = read_file_ast(&search_result.path, encoding)?;
+
+  let edits = match ast_edits_from_search(search_result, &content, &[selected]) {
+    Some(edits) => edits,
+    None => {
+      let paths = [PathBuf::from(&search_result.path)];
+      let results = run_ast_grep(&paths, &search_text_state.text, Some(&replace_text_state.text), ast_grep_state)?;
+      ast_edits_from_results(&results, &[selected])
+    },
+  };
+  let Some(edit) = edits.into_iter().next() else {
+    return Err(eyre!("changed since the search, search again"));
+  };
+
+  let removed_text = content[edit.range.clone()].to_string();
+  content.replace_range(edit.range.clone(), &edit.text);
+  encoding::write_file(&search_result.path, &content, file_encoding)?;
+
+  shift_ast_matches(search_result, &edit, &removed_text, &content);
+  Ok(())
+}
+
+/// Edits for `matches` from the replacements captured at search time, None when the file changed since the search
+/// or a match has no replacement.
+fn ast_edits_from_search(search_result: &SearchResultState, content: &str, matches: &[&Match]) -> Option<Vec<AstEdit>> {
+  if search_result.content_hash != Some(content_hash(content)) {
+    return None;
+  }
+
+  matches
+    .iter()
+    .map(|m| {
+      let offsets = m.replacement_offsets.as_ref()?;
+      Some(AstEdit { range: offsets.start..offsets.end, text: m.replacement.clone()? })
+    })
+    .collect()
+}
+
+/// Edits for `matches` from a fresh ast-grep run, picking each result by the byte range of its matched node.
+fn ast_edits_from_results(results: &[AstGrepOutput], matches: &[&Match]) -> Vec<AstEdit> {
+  let selected: HashSet<(usize, usize)> =
+    matches.iter().filter_map(|m| m.byte_range.as_ref()).map(|range| (range.start, range.end)).collect();
+
+  results
+    .iter()
+    .filter(|result| selected.contains(&(result.range.byte_offset.start, result.range.byte_offset.end)))
+    .filter_map(|result| {
+      let offsets = result.replacement_offsets.as_ref()?;
+      Some(AstEdit { range: offsets.start..offsets.end, text: result.replacement.clone()? })
+    })
+    .collect()
+}
+
+fn apply_ast_edits(content: &mut String, mut edits: Vec<AstEdit>) {
+  edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+  for edit in edits {
+    content.replace_range(edit.range, &edit.text);
+  }
+}
+
+/// Moves the matches after `edit` by the bytes and lines it added or removed and rehashes the file, so the
+/// replacements captured at search time keep applying. Matches overlapping the edit lose their replacement.
+fn shift_ast_matches(search_result: &mut SearchResultState, edit: &AstEdit, removed_text: &str, new_content: &str) {
+  let byte_delta = edit.text.len() as isize - removed_text.len() as isize;
+  let line_delta = edit.text.matches('\n').count() as isize - removed_text.matches('\n').count() as isize;
+
+  for m in search_result.matches.iter_mut() {
+    let Some(range) = m.byte_range.clone() else {
+      continue;
+    };
+    if range.start >= edit.range.end {
+      m.byte_range = Some(range.start.saturating_add_signed(byte_delta)..range.end.saturating_add_signed(byte_delta));
+      m.absolute_offset = m.absolute_offset.saturating_add_signed(byte_delta);
+      if let Some(offsets) = m.replacement_offsets.as_mut() {
+        offsets.start = offsets.start.saturating_add_signed(byte_delta);
+        offsets.end = offsets.end.saturating_add_signed(byte_delta);
+      }
+      m.line_number = m.line_number.saturating_add_signed(line_delta);
+      for submatch in m.submatches.iter_mut() {
+        submatch.line_start = submatch.line_start.saturating_add_signed(line_delta);
+        submatch.line_end = submatch.line_end.saturating_add_signed(line_delta);
+      }
+    } else if range.end > edit.range.start {
+      m.replacement_offsets = None;
+    }
+  }
+
+  search_result.content_hash = Some(content_hash(new_content));
+}
+
+fn canonical_path(path: &str) -> PathBuf {
+  fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Hash of a file's content, checked before reusing the replacement offsets captured at search time.
+pub fn content_hash(content: &str) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  content.hash(&mut hasher);
+  hasher.finish()
 }
 
 pub fn replace_file_normal(
   search_result: &crate::redux::state::SearchResultState,
   search_text_state: &crate::redux::state::SearchTextState,
   replace_text_state: &crate::redux::state::ReplaceTextState,
-) {
-  let file_path = &search_result.path;
+  encoding: Option<&'static Encoding>,
+) -> io::Result<()> {
+  let new_content = replaced_content_normal(search_result, search_text_state, replace_text_state, encoding)?;
+  fs::write(&search_result.path, new_content)
+}
 
-  let content = fs::read_to_string(file_path).expect("Unable to read file");
+/// New bytes of a file with the replacement applied, in the file's own encoding, computed without writing them.
+pub fn replaced_content_normal(
+  search_result: &SearchResultState,
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  encoding: Option<&'static Encoding>,
+) -> io::Result<Vec<u8>> {
+  let (content, file_encoding) = encoding::read_file(&search_result.path, encoding)?;
   let lines: Vec<&str> = content.lines().collect();
 
   let new_content = if replace_text_state.kind == ReplaceTextKind::DeleteLine {
@@ -64,42 +367,65 @@ pub fn replace_file_normal(
       .collect::<Vec<&str>>()
       .join("\n")
   } else {
-    let re = get_search_regex(&search_text_state.text, &search_text_state.kind);
+    let re = get_replace_regex(search_text_state, replace_text_state);
 
     re.replace_all(&content, |caps: &regex::Captures| {
-      let matched_text = caps.get(0).unwrap().as_str();
-      apply_replace(matched_text, &replace_text_state.text, &replace_text_state.kind)
+      apply_replace_captures(caps, &search_text_state.kind, replace_text_state)
     })
     .to_string()
   };
 
-  fs::write(file_path, new_content).expect("Unable to write file");
+  encoding::encode(&new_content, file_encoding)
 }
 
 pub fn get_search_regex(search_text: &str, search_kind: &SearchTextKind) -> regex::Regex {
+  try_get_search_regex(search_text, search_kind).expect("Invalid regex")
+}
+
+/// Only fails in Regex mode, where the text is a user supplied pattern.
+pub fn try_get_search_regex(search_text: &str, search_kind: &SearchTextKind) -> Result<regex::Regex, regex::Error> {
   let escaped_search_text = regex::escape(search_text);
 
   match search_kind {
-    SearchTextKind::Simple => {
-      RegexBuilder::new(&escaped_search_text).case_insensitive(true).build().expect("Invalid regex")
-    },
-    SearchTextKind::MatchCase => {
-      RegexBuilder::new(&escaped_search_text).case_insensitive(false).build().expect("Invalid regex")
-    },
+    SearchTextKind::Simple => RegexBuilder::new(&escaped_search_text).case_insensitive(true).build(),
+    SearchTextKind::MatchCase => RegexBuilder::new(&escaped_search_text).case_insensitive(false).build(),
     SearchTextKind::MatchWholeWord => {
-      RegexBuilder::new(&format!(r"\b{escaped_search_text}\b")).case_insensitive(true).build().expect("Invalid regex")
+      RegexBuilder::new(&format!(r"\b{escaped_search_text}\b")).case_insensitive(true).build()
     },
     SearchTextKind::MatchCaseWholeWord => {
-      RegexBuilder::new(&format!(r"\b{escaped_search_text}\b")).case_insensitive(false).build().expect("Invalid regex")
-    },
-    SearchTextKind::Regex => {
-      RegexBuilder::new(&escaped_search_text).case_insensitive(true).build().expect("Invalid regex")
+      RegexBuilder::new(&format!(r"\b{escaped_search_text}\b")).case_insensitive(false).build()
     },
+    SearchTextKind::Regex => RegexBuilder::new(search_text).build(),
     #[cfg(feature = "ast_grep")]
     SearchTextKind::AstGrep => unreachable!("AST Grep doesn't use regex"),
   }
 }
 
+/// Builds the regex used when writing replacements, which for `SmartCase` matches every case variant of the
+/// search term instead of the literal text.
+pub fn get_replace_regex(search_text_state: &SearchTextState, replace_text_state: &ReplaceTextState) -> regex::Regex {
+  try_get_replace_regex(search_text_state, replace_text_state).expect("Invalid regex")
+}
+
+/// Same as `get_replace_regex`, for callers that can run before ripgrep has validated a user supplied regex.
+pub fn try_get_replace_regex(
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+) -> Result<regex::Regex, regex::Error> {
+  if replace_text_state.kind != ReplaceTextKind::SmartCase || search_text_state.kind == SearchTextKind::Regex {
+    return try_get_search_regex(&search_text_state.text, &search_text_state.kind);
+  }
+
+  let pattern = smart_case_pattern(&search_text_state.text);
+  let (pattern, case_insensitive) = match search_text_state.kind {
+    SearchTextKind::MatchCase => (pattern, false),
+    SearchTextKind::MatchWholeWord => (format!(r"\b(?:{pattern})\b"), true),
+    SearchTextKind::MatchCaseWholeWord => (format!(r"\b(?:{pattern})\b"), false),
+    _ => (pattern, true),
+  };
+  RegexBuilder::new(&pattern).case_insensitive(case_insensitive).build()
+}
+
 pub fn apply_replace(matched_text: &str, replace_text: &str, replace_kind: &ReplaceTextKind) -> String {
   match replace_kind {
     ReplaceTextKind::Simple => replace_text.to_string(),
@@ -122,7 +448,566 @@ pub fn apply_replace(matched_text: &str, replace_text: &str, replace_kind: &Repl
         replace_text.to_lowercase()
       }
     },
+    ReplaceTextKind::SmartCase => to_case_style(replace_text, detect_case_style(matched_text)),
     #[cfg(feature = "ast_grep")]
     ReplaceTextKind::AstGrep => unreachable!(),
   }
 }
+
+/// The project root a result path belongs to, the innermost one when roots are nested, falling back to the first root
+/// for paths outside all of them.
+pub fn root_for_path(path: &str, project_roots: &[PathBuf]) -> PathBuf {
+  project_roots
+    .iter()
+    .filter(|root| Path::new(path).starts_with(root))
+    .max_by_key(|root| root.components().count())
+    .or(project_roots.first())
+    .cloned()
+    .unwrap_or_default()
+}
+
+/// Path of a search result relative to the project root, which is the part matched in file path mode.
+pub fn relative_path<'a>(path: &'a str, project_root: &Path) -> &'a str {
+  Path::new(path).strip_prefix(project_root).ok().and_then(|p| p.to_str()).unwrap_or(path)
+}
+
+/// Whether two paths name the same file, ignoring `.` components, so `./src/a.rs` and `src/a.rs` are equal.
+pub fn same_path(a: &Path, b: &Path) -> bool {
+  path_components(a) == path_components(b)
+}
+
+/// Whether `path` is `base` or inside it, ignoring `.` components like `same_path`.
+pub fn is_under(path: &Path, base: &Path) -> bool {
+  path_components(path).starts_with(&path_components(base))
+}
+
+fn path_components(path: &Path) -> Vec<std::path::Component<'_>> {
+  path.components().filter(|component| *component != std::path::Component::CurDir).collect()
+}
+
+/// Merges fresh results for files changed on disk into the list. Listed files are updated in place and new matches
+/// appended, so the selected position keeps pointing at the same file. Listed files that are gone or stopped
+/// matching are marked removed.
+pub fn merge_refreshed_results(list: &mut Vec<SearchResultState>, changed: &[PathBuf], fresh: Vec<SearchResultState>) {
+  for path in changed {
+    let fresh_result = fresh.iter().find(|result| same_path(Path::new(&result.path), path));
+    let listed = list
+      .iter_mut()
+      .filter(|result| same_path(Path::new(&result.path), path))
+      .filter(|result| result.kind == SearchResultKind::Content || !path.exists());
+
+    let mut is_listed = false;
+    for result in listed {
+      is_listed = true;
+      match fresh_result {
+        Some(fresh_result) if result.kind == SearchResultKind::Content => {
+          *result = SearchResultState { path: result.path.clone(), ..fresh_result.clone() };
+        },
+        _ => {
+          *result =
+            SearchResultState { matches: Vec::new(), total_matches: 0, removed: true, ..std::mem::take(result) };
+        },
+      }
+    }
+    if let (Some(fresh_result), false) = (fresh_result, is_listed) {
+      list.push(fresh_result.clone());
+    }
+  }
+
+  for (index, result) in list.iter_mut().enumerate() {
+    result.index = Some(index);
+  }
+}
+
+pub fn get_renamed_path(
+  relative_path: &str,
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+) -> String {
+  let Ok(re) = try_get_replace_regex(search_text_state, replace_text_state) else {
+    return relative_path.to_string();
+  };
+  re.replace_all(relative_path, |caps: &regex::Captures| {
+    apply_replace_captures(caps, &search_text_state.kind, replace_text_state)
+  })
+  .into_owned()
+}
+
+/// Moves a file path result to its replaced path, creating missing parent directories and going through
+/// `git mv` when the project is a git repository so the rename is staged.
+pub fn rename_file_path(
+  search_result: &SearchResultState,
+  search_text_state: &SearchTextState,
+  replace_text_state: &ReplaceTextState,
+  project_root: &Path,
+) -> io::Result<()> {
+  let old_path = relative_path(&search_result.path, project_root);
+  let new_path = get_renamed_path(old_path, search_text_state, replace_text_state);
+  if new_path.is_empty() || new_path == old_path {
+    return Ok(());
+  }
+
+  let target = project_root.join(&new_path);
+  if target.exists() {
+    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{new_path} already exists")));
+  }
+  if let Some(parent) = target.parent() {
+    fs::create_dir_all(parent)?;
+  }
+
+  if is_git_repo(project_root.to_path_buf()) {
+    let output = Command::new("git").args(["mv", old_path, &new_path]).current_dir(project_root).output()?;
+    if output.status.success() {
+      return Ok(());
+    }
+    log::warn!("git mv failed, falling back to rename: {}", String::from_utf8_lossy(&output.stderr));
+  }
+
+  fs::rename(project_root.join(old_path), target)
+}
+
+/// Replacement for a regex match: in Regex mode capture groups and case escapes are expanded first, then the
+/// replace kind is applied to the result.
+pub fn apply_replace_captures(
+  caps: &regex::Captures,
+  search_kind: &SearchTextKind,
+  replace_text_state: &ReplaceTextState,
+) -> String {
+  let matched_text = caps.get(0).map_or("", |m| m.as_str());
+  if *search_kind == SearchTextKind::Regex {
+    let expanded = expand_replacement(caps, &replace_text_state.text);
+    apply_replace(matched_text, &expanded, &replace_text_state.kind)
+  } else {
+    apply_replace(matched_text, &replace_text_state.text, &replace_text_state.kind)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CaseTransform {
+  Upper,
+  Lower,
+}
+
+/// Expands `$1`/`${name}` capture references along with the sed/Vim style case escapes:
+/// `\U` and `\L` upper- or lower-case everything up to `\E`, `\u` and `\l` only the next character.
+pub fn expand_replacement(caps: &regex::Captures, replace_text: &str) -> String {
+  let mut result = String::new();
+  let mut segment = String::new();
+  let mut transform: Option<CaseTransform> = None;
+  let mut next_char: Option<CaseTransform> = None;
+
+  let mut flush = |segment: &mut String, transform: Option<CaseTransform>, next_char: &mut Option<CaseTransform>| {
+    if segment.is_empty() {
+      return;
+    }
+    let mut expanded = String::new();
+    caps.expand(segment, &mut expanded);
+    segment.clear();
+
+    let expanded = match transform {
+      Some(CaseTransform::Upper) => expanded.to_uppercase(),
+      Some(CaseTransform::Lower) => expanded.to_lowercase(),
+      None => expanded,
+    };
+    let mut chars = expanded.chars();
+    match (next_char.take(), chars.next()) {
+      (Some(CaseTransform::Upper), Some(first)) => result.extend(first.to_uppercase().chain(chars)),
+      (Some(CaseTransform::Lower), Some(first)) => result.extend(first.to_lowercase().chain(chars)),
+      (pending, _) => {
+        *next_char = if expanded.is_empty() { pending } else { None };
+        result.push_str(&expanded);
+      },
+    }
+  };
+
+  let mut chars = replace_text.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      segment.push(c);
+      continue;
+    }
+    match chars.peek() {
+      Some('U') | Some('L') | Some('E') | Some('u') | Some('l') => {
+        flush(&mut segment, transform, &mut next_char);
+        match chars.next() {
+          Some('U') => transform = Some(CaseTransform::Upper),
+          Some('L') => transform = Some(CaseTransform::Lower),
+          Some('E') => transform = None,
+          Some('u') => next_char = Some(CaseTransform::Upper),
+          Some('l') => next_char = Some(CaseTransform::Lower),
+          _ => unreachable!(),
+        }
+      },
+      Some('\\') => {
+        chars.next();
+        segment.push('\\');
+      },
+      _ => segment.push(c),
+    }
+  }
+  flush(&mut segment, transform, &mut next_char);
+
+  result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaseStyle {
+  Camel,
+  Pascal,
+  Snake,
+  ScreamingSnake,
+  Kebab,
+}
+
+/// Splits an identifier into lowercase words, treating `_`, `-`, whitespace and camel humps as boundaries.
+pub fn split_words(text: &str) -> Vec<String> {
+  let mut words = Vec::new();
+  let mut current = String::new();
+  let chars: Vec<char> = text.chars().collect();
+
+  for (i, &c) in chars.iter().enumerate() {
+    if c == '_' || c == '-' || c.is_whitespace() {
+      if !current.is_empty() {
+        words.push(std::mem::take(&mut current));
+      }
+      continue;
+    }
+
+    if c.is_uppercase() && !current.is_empty() {
+      let prev = chars[i - 1];
+      let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+      if !prev.is_uppercase() || next_is_lower {
+        words.push(std::mem::take(&mut current));
+      }
+    }
+    current.extend(c.to_lowercase());
+  }
+
+  if !current.is_empty() {
+    words.push(current);
+  }
+  words
+}
+
+pub fn detect_case_style(text: &str) -> CaseStyle {
+  let has_lowercase = text.chars().any(char::is_lowercase);
+  if text.contains('_') {
+    if has_lowercase {
+      CaseStyle::Snake
+    } else {
+      CaseStyle::ScreamingSnake
+    }
+  } else if text.contains('-') {
+    CaseStyle::Kebab
+  } else if !has_lowercase {
+    CaseStyle::ScreamingSnake
+  } else if text.chars().next().is_some_and(char::is_uppercase) {
+    CaseStyle::Pascal
+  } else {
+    CaseStyle::Camel
+  }
+}
+
+pub fn to_case_style(text: &str, style: CaseStyle) -> String {
+  let words = split_words(text);
+  let capitalize = |word: &String| {
+    let mut chars = word.chars();
+    match chars.next() {
+      Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+      None => String::new(),
+    }
+  };
+
+  match style {
+    CaseStyle::Camel => words.iter().enumerate().map(|(i, w)| if i == 0 { w.clone() } else { capitalize(w) }).collect(),
+    CaseStyle::Pascal => words.iter().map(capitalize).collect(),
+    CaseStyle::Snake => words.join("_"),
+    CaseStyle::ScreamingSnake => words.join("_").to_uppercase(),
+    CaseStyle::Kebab => words.join("-"),
+  }
+}
+
+/// Every case variant of `text`, longest first so the regex alternation prefers the most specific spelling.
+pub fn smart_case_variants(text: &str) -> Vec<String> {
+  let mut variants: Vec<String> =
+    [CaseStyle::Camel, CaseStyle::Pascal, CaseStyle::Snake, CaseStyle::ScreamingSnake, CaseStyle::Kebab]
+      .into_iter()
+      .map(|style| to_case_style(text, style))
+      .filter(|variant| !variant.is_empty())
+      .collect();
+  variants.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+  variants.dedup();
+  variants
+}
+
+pub fn smart_case_pattern(text: &str) -> String {
+  smart_case_variants(text).iter().map(|variant| regex::escape(variant)).collect::<Vec<_>>().join("|")
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_split_words() {
+    assert_eq!(split_words("userId"), vec!["user", "id"]);
+    assert_eq!(split_words("USER_ID"), vec!["user", "id"]);
+    assert_eq!(split_words("HTTPServer"), vec!["http", "server"]);
+    assert_eq!(split_words("user-id"), vec!["user", "id"]);
+  }
+
+  #[test]
+  fn test_smart_case_variants() {
+    assert_eq!(smart_case_variants("userId"), vec!["USER_ID", "user-id", "user_id", "UserId", "userId"]);
+  }
+
+  #[test]
+  fn test_smart_case_replace() {
+    let kind = ReplaceTextKind::SmartCase;
+    assert_eq!(apply_replace("user_id", "accountId", &kind), "account_id");
+    assert_eq!(apply_replace("UserId", "accountId", &kind), "AccountId");
+    assert_eq!(apply_replace("USER_ID", "accountId", &kind), "ACCOUNT_ID");
+    assert_eq!(apply_replace("user-id", "accountId", &kind), "account-id");
+    assert_eq!(apply_replace("userId", "account_id", &kind), "accountId");
+  }
+
+  #[test]
+  fn test_expand_replacement_case_escapes() {
+    let re = get_search_regex(r"(\w+)_(\w+)", &SearchTextKind::Regex);
+    let caps = re.captures("user_name").unwrap();
+    assert_eq!(expand_replacement(&caps, r"${2}_$1"), "name_user");
+    assert_eq!(expand_replacement(&caps, r"\U$1\E_$2"), "USER_name");
+    assert_eq!(expand_replacement(&caps, r"\u$1\u$2"), "UserName");
+    assert_eq!(expand_replacement(&caps, r"\L\u${2}X"), "Namex");
+    assert_eq!(expand_replacement(&caps, r"a\\b"), r"a\b");
+  }
+
+  #[test]
+  fn test_smart_case_regex() {
+    let search = SearchTextState { text: "userId".to_string(), kind: SearchTextKind::MatchCase };
+    let replace = ReplaceTextState { text: "accountId".to_string(), kind: ReplaceTextKind::SmartCase };
+    let re = get_replace_regex(&search, &replace);
+    let matches: Vec<&str> = re.find_iter("user_id UserId USER_ID user-id userid").map(|m| m.as_str()).collect();
+    assert_eq!(matches, vec!["user_id", "UserId", "USER_ID", "user-id"]);
+  }
+
+  #[test]
+  fn test_root_for_path() {
+    let roots = vec![PathBuf::from("app"), PathBuf::from("app-lib")];
+    assert_eq!(root_for_path("app-lib/src/lib.rs", &roots), PathBuf::from("app-lib"));
+    assert_eq!(root_for_path("app/src/main.rs", &roots), PathBuf::from("app"));
+    assert_eq!(root_for_path("elsewhere/main.rs", &roots), PathBuf::from("app"));
+    assert_eq!(root_for_path("app/src/main.rs", &[]), PathBuf::new());
+
+    let nested = vec![PathBuf::from("app"), PathBuf::from("app/lib")];
+    assert_eq!(root_for_path("app/lib/src/lib.rs", &nested), PathBuf::from("app/lib"));
+    assert_eq!(root_for_path("app/src/main.rs", &nested), PathBuf::from("app"));
+  }
+
+  #[test]
+  fn test_get_renamed_path() {
+    let search = |text: &str, kind: SearchTextKind| SearchTextState { text: text.to_string(), kind };
+    let replace = |text: &str| ReplaceTextState { text: text.to_string(), kind: ReplaceTextKind::Simple };
+
+    let renamed = get_renamed_path("src/old_name.rs", &search("old", SearchTextKind::Simple), &replace("new"));
+    assert_eq!(renamed, "src/new_name.rs");
+    let renamed =
+      get_renamed_path("src/old_name.rs", &search(r"(\w+)_name", SearchTextKind::Regex), &replace("$1/mod"));
+    assert_eq!(renamed, "src/old/mod.rs");
+    let renamed = get_renamed_path("old.txt", &search("old.txt", SearchTextKind::Simple), &replace(""));
+    assert_eq!(renamed, "");
+  }
+
+  #[test]
+  fn test_rename_file_path() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let search_text = SearchTextState { text: "old".to_string(), kind: SearchTextKind::Simple };
+    let replace = |text: &str| ReplaceTextState { text: text.to_string(), kind: ReplaceTextKind::Simple };
+    let result =
+      |name: &str| SearchResultState { path: dir.join(name).to_string_lossy().to_string(), ..Default::default() };
+    let git = |args: &[&str]| {
+      let output = Command::new("git")
+        .arg("-C")
+        .arg(&dir)
+        .args(["-c", "user.name=serpl", "-c", "user.email=serpl@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+      assert!(output.status.success());
+      String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Outside git the file is moved, into a directory that doesn't exist yet.
+    fs::write(dir.join("old.txt"), "content").unwrap();
+    rename_file_path(&result("old.txt"), &search_text, &replace("new/dir/new"), &dir).unwrap();
+    assert!(!dir.join("old.txt").exists());
+    assert_eq!(fs::read_to_string(dir.join("new/dir/new.txt")).unwrap(), "content");
+
+    // A name replaced with nothing is left alone, a taken name is refused.
+    fs::write(dir.join("old.txt"), "content").unwrap();
+    let search_name = SearchTextState { text: "old.txt".to_string(), kind: SearchTextKind::Simple };
+    rename_file_path(&result("old.txt"), &search_name, &replace(""), &dir).unwrap();
+    assert!(dir.join("old.txt").exists());
+    fs::write(dir.join("taken.txt"), "taken").unwrap();
+    let error = rename_file_path(&result("old.txt"), &search_text, &replace("taken"), &dir).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+
+    // In git a tracked file is renamed with `git mv`, an untracked one falls back to a plain rename.
+    git(&["init", "-q"]);
+    git(&["add", "old.txt"]);
+    git(&["commit", "-q", "-m", "initial"]);
+    fs::write(dir.join("old_untracked.txt"), "untracked").unwrap();
+    rename_file_path(&result("old.txt"), &search_text, &replace("renamed"), &dir).unwrap();
+    rename_file_path(&result("old_untracked.txt"), &search_text, &replace("renamed"), &dir).unwrap();
+    assert_eq!(git(&["diff", "--cached", "--name-status", "-M"]), "R100\told.txt\trenamed.txt\n");
+    assert_eq!(fs::read_to_string(dir.join("renamed_untracked.txt")).unwrap(), "untracked");
+    assert!(!dir.join("old_untracked.txt").exists());
+  }
+
+  #[test]
+  fn test_ast_grep_args() {
+    let pattern_state = AstGrepState { lang: Some("rust".to_string()), rule_file: None };
+    let expected = vec!["run", "-p", "foo($A)", "--lang", "rust", "-r", "bar($A)", "--json=compact"];
+    assert_eq!(ast_grep_args("foo($A)", Some("bar($A)"), &pattern_state), expected);
+
+    let rule_state = AstGrepState { lang: None, rule_file: Some(PathBuf::from("rules/no-unwrap.yml")) };
+    assert_eq!(ast_grep_args("", None, &rule_state), vec!["scan", "-r", "rules/no-unwrap.yml", "--json=compact"]);
+
+    let config_state = AstGrepState { lang: None, rule_file: Some(PathBuf::from("sgconfig.yml")) };
+    assert_eq!(ast_grep_args("", None, &config_state), vec!["scan", "-c", "sgconfig.yml", "--json=compact"]);
+  }
+
+  #[test]
+  fn test_replace_file_ast_from_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("replace.rs");
+    let content = "let a = foo(1);\nlet b = foo(2);\n";
+    fs::write(&path, content).unwrap();
+
+    let ast_match = |start: usize, replacement: &str| {
+      Match {
+        replacement: Some(replacement.to_string()),
+        replacement_offsets: Some(ReplacementOffsets { start, end: start + 6 }),
+        ..Default::default()
+      }
+    };
+    let mut search_result = SearchResultState {
+      path: path.to_string_lossy().to_string(),
+      matches: vec![ast_match(8, "bar(1)"), ast_match(24, "bar(2)")],
+      content_hash: Some(content_hash("outdated")),
+      ..Default::default()
+    };
+    assert!(!replace_file_ast_from_search(&search_result, None).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+    search_result.content_hash = Some(content_hash(content));
+    assert!(replace_file_ast_from_search(&search_result, None).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "let a = bar(1);\nlet b = bar(2);\n");
+
+    // ast-grep's offsets count the byte order mark, which is written back once.
+    let with_bom = format!("\u{feff}{content}");
+    fs::write(&path, &with_bom).unwrap();
+    search_result.matches = vec![ast_match(11, "bar(1)"), ast_match(27, "bar(2)")];
+    search_result.content_hash = Some(content_hash(&with_bom));
+    assert!(replace_file_ast_from_search(&search_result, None).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "\u{feff}let a = bar(1);\nlet b = bar(2);\n");
+
+    fs::write(&path, "let a = foo(1);\0").unwrap();
+    let error = replace_file_ast_from_search(&search_result, None).unwrap_err();
+    assert_eq!(
+      file_replace_result(&search_result.path, Err(error)).outcome,
+      ReplaceOutcome::Skipped("binary file".to_string())
+    );
+  }
+
+  #[test]
+  fn test_merge_refreshed_results() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+    let result = |name: &str, total_matches: usize| {
+      SearchResultState { path: path(name), total_matches, matches: vec![Match::default()], ..Default::default() }
+    };
+    fs::write(path("changed.txt"), "foo foo\n").unwrap();
+    fs::write(path("unmatched.txt"), "bar\n").unwrap();
+    fs::write(path("new.txt"), "foo\n").unwrap();
+
+    let mut list = vec![result("changed.txt", 1), result("deleted.txt", 1), result("unmatched.txt", 1)];
+    let changed: Vec<PathBuf> =
+      ["changed.txt", "deleted.txt", "unmatched.txt", "new.txt"].iter().map(|name| dir.join(name)).collect();
+    merge_refreshed_results(&mut list, &changed, vec![result("changed.txt", 2), result("new.txt", 1)]);
+
+    let summary: Vec<(String, usize, bool, Option<usize>)> =
+      list.iter().map(|r| (r.path.clone(), r.total_matches, r.removed, r.index)).collect();
+    assert_eq!(summary, vec![
+      (path("changed.txt"), 2, false, Some(0)),
+      (path("deleted.txt"), 0, true, Some(1)),
+      (path("unmatched.txt"), 0, true, Some(2)),
+      (path("new.txt"), 1, false, Some(3)),
+    ]);
+  }
+
+  #[test]
+  fn test_replace_file_normal_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("replace.txt");
+    let search_result = SearchResultState { path: path.to_string_lossy().to_string(), ..Default::default() };
+    let search_text = SearchTextState { text: "foo".to_string(), kind: SearchTextKind::Simple };
+    let replace_text = ReplaceTextState { text: "bar".to_string(), kind: ReplaceTextKind::Simple };
+    let outcome = |search_result: &SearchResultState| {
+      let result = replace_file_normal(search_result, &search_text, &replace_text, None);
+      file_replace_result(&search_result.path, result).outcome
+    };
+
+    assert_eq!(outcome(&search_result), ReplaceOutcome::Skipped("file no longer exists".to_string()));
+
+    fs::write(&path, [0x66, 0x6f, 0x6f, 0xff]).unwrap();
+    let not_utf8 = "not valid UTF-8, set its encoding with --encoding".to_string();
+    assert_eq!(outcome(&search_result), ReplaceOutcome::Skipped(not_utf8));
+
+    fs::write(&path, [0x66, 0x6f, 0x6f, 0x00]).unwrap();
+    assert_eq!(outcome(&search_result), ReplaceOutcome::Skipped("binary file".to_string()));
+
+    fs::write(&path, "foo\n").unwrap();
+    assert_eq!(outcome(&search_result), ReplaceOutcome::Replaced);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "bar\n");
+  }
+
+  #[test]
+  fn test_replace_match_ast() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("replace.rs");
+    let content = "let a = foo(1) + foo(2);\nlet b = foo(3);\n";
+    fs::write(&path, content).unwrap();
+
+    let ast_match = |start: usize, line_number: usize, replacement: &str| {
+      Match {
+        line_number,
+        replacement: Some(replacement.to_string()),
+        replacement_offsets: Some(ReplacementOffsets { start, end: start + 6 }),
+        byte_range: Some(start..start + 6),
+        ..Default::default()
+      }
+    };
+    let mut search_result = SearchResultState {
+      path: path.to_string_lossy().to_string(),
+      matches: vec![ast_match(8, 1, "bar(1)\n"), ast_match(17, 1, "bar(2)"), ast_match(33, 2, "bar(3)")],
+      content_hash: Some(content_hash(content)),
+      ..Default::default()
+    };
+    let (search_text, replace_text) = (SearchTextState::default(), ReplaceTextState::default());
+    let ast_grep_state = AstGrepState::default();
+
+    // Replacing the first match leaves its neighbour on the same line alone and shifts the later matches.
+    replace_match_ast(&mut search_result, 0, &search_text, &replace_text, &ast_grep_state, None).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "let a = bar(1)\n + foo(2);\nlet b = foo(3);\n");
+    assert_eq!(search_result.matches[1].byte_range, Some(18..24));
+    assert_eq!(search_result.matches[2].line_number, 3);
+
+    replace_match_ast(&mut search_result, 2, &search_text, &replace_text, &ast_grep_state, None).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "let a = bar(1)\n + foo(2);\nlet b = bar(3);\n");
+  }
+}
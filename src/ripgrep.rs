@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+pub mod native;
+
 #[derive(Deserialize, Debug)]
 pub struct RipgrepOutput {
   #[serde(rename = "type")]
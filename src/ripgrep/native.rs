@@ -0,0 +1,270 @@
+use std::{
+  collections::VecDeque,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicUsize, Ordering},
+  time::Instant,
+};
+
+use grep::{
+  matcher::Matcher,
+  regex::{RegexMatcher, RegexMatcherBuilder},
+  searcher::{Searcher, SearcherBuilder, Sink, SinkContext, SinkMatch},
+};
+use ignore::{WalkBuilder, WalkState};
+
+use super::RipgrepLines;
+use crate::redux::{
+  state::{Match, Metadata, SearchResultKind, SearchResultState, SearchTextKind, SearchTextState, SubMatch},
+  utils::smart_case_pattern,
+};
+
+const CONTEXT_LINES: usize = 3;
+
+/// Text search over `paths` with ripgrep's library crates, producing the same results as `rg --json -C 3`.
+/// The tree is walked in parallel with the ignore files ripgrep honors, each file with matches is handed to
+/// `on_result` once searched. Files are decoded the way a replace decodes them, so offsets index the decoded text, and
+/// binary files or files that aren't valid in their encoding are skipped like a replace skips them.
+pub fn search(
+  paths: &[PathBuf],
+  search_text_state: &SearchTextState,
+  is_smart_case: bool,
+  encoding: Option<&'static encoding_rs::Encoding>,
+  on_result: &(dyn Fn(SearchResultState) + Sync),
+) -> Result<Metadata, grep::regex::Error> {
+  let started = Instant::now();
+  let matcher = build_matcher(search_text_state, is_smart_case)?;
+  let Some(walker) = walker(paths) else {
+    return Ok(Metadata::default());
+  };
+
+  let searches = AtomicUsize::new(0);
+  let searches_with_match = AtomicUsize::new(0);
+  let matched_lines = AtomicUsize::new(0);
+  let matches = AtomicUsize::new(0);
+  walker.build_parallel().run(|| {
+    let matcher = &matcher;
+    let (searches, searches_with_match, matched_lines, matches) =
+      (&searches, &searches_with_match, &matched_lines, &matches);
+    let mut searcher = SearcherBuilder::new()
+      .line_number(true)
+      .before_context(CONTEXT_LINES)
+      .after_context(CONTEXT_LINES)
+      .bom_sniffing(false)
+      .build();
+
+    Box::new(move |entry| {
+      let Ok(entry) = entry else {
+        return WalkState::Continue;
+      };
+      if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+        return WalkState::Continue;
+      }
+
+      searches.fetch_add(1, Ordering::Relaxed);
+      let text = match fs::read(entry.path()).and_then(|bytes| crate::encoding::decode(&bytes, encoding)) {
+        Ok((text, _)) => text,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+          log::warn!("Skipped {}: {}", entry.path().display(), e);
+          return WalkState::Continue;
+        },
+        Err(e) => {
+          log::error!("Failed to read {}: {}", entry.path().display(), e);
+          return WalkState::Continue;
+        },
+      };
+
+      let mut sink = FileSink::new(matcher, entry.path());
+      if let Err(e) = searcher.search_slice(matcher, text.as_bytes(), &mut sink) {
+        log::error!("Failed to search {}: {}", entry.path().display(), e);
+        return WalkState::Continue;
+      }
+      if !sink.result.matches.is_empty() {
+        searches_with_match.fetch_add(1, Ordering::Relaxed);
+        matched_lines.fetch_add(sink.result.matches.len(), Ordering::Relaxed);
+        matches.fetch_add(sink.result.total_matches, Ordering::Relaxed);
+        on_result(sink.result);
+      }
+      WalkState::Continue
+    })
+  });
+
+  Ok(Metadata {
+    elapsed_time: started.elapsed().as_nanos() as u64,
+    matched_lines: matched_lines.into_inner(),
+    matches: matches.into_inner(),
+    searches: searches.into_inner(),
+    searches_with_match: searches_with_match.into_inner(),
+  })
+}
+
+/// Files under `paths` that a search would look at, the same list as `rg --files`.
+pub fn list_files(paths: &[PathBuf]) -> Vec<String> {
+  let Some(walker) = walker(paths) else {
+    return Vec::new();
+  };
+
+  walker
+    .build()
+    .filter_map(Result::ok)
+    .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+    .map(|entry| entry.path().to_string_lossy().to_string())
+    .collect()
+}
+
+fn walker(paths: &[PathBuf]) -> Option<WalkBuilder> {
+  let (first, rest) = paths.split_first()?;
+  let mut builder = WalkBuilder::new(first);
+  for path in rest {
+    builder.add(path);
+  }
+  Some(builder)
+}
+
+/// Matcher for the search mode, following the ripgrep flags the external search passes for it.
+fn build_matcher(search_text_state: &SearchTextState, is_smart_case: bool) -> Result<RegexMatcher, grep::regex::Error> {
+  let pattern =
+    if is_smart_case { smart_case_pattern(&search_text_state.text) } else { search_text_state.text.clone() };
+
+  let mut builder = RegexMatcherBuilder::new();
+  builder.line_terminator(Some(b'\n'));
+  match search_text_state.kind {
+    SearchTextKind::Regex => return builder.build(&search_text_state.text),
+    SearchTextKind::MatchCase => {},
+    SearchTextKind::MatchWholeWord => {
+      builder.word(true).case_insensitive(true);
+    },
+    SearchTextKind::MatchCaseWholeWord => {
+      builder.word(true);
+    },
+    SearchTextKind::Simple if is_smart_case => {
+      builder.case_insensitive(true);
+    },
+    SearchTextKind::Simple => {
+      builder.case_insensitive(true).fixed_strings(true);
+    },
+    #[cfg(feature = "ast_grep")]
+    SearchTextKind::AstGrep => {},
+  }
+  builder.build(&pattern)
+}
+
+/// Collects the matches of one file, keeping up to three lines of context around each.
+struct FileSink<'a> {
+  matcher: &'a RegexMatcher,
+  result: SearchResultState,
+  context_buffer: VecDeque<String>,
+}
+
+impl<'a> FileSink<'a> {
+  fn new(matcher: &'a RegexMatcher, path: &Path) -> Self {
+    let result = SearchResultState {
+      path: path.to_string_lossy().to_string(),
+      kind: SearchResultKind::Content,
+      ..Default::default()
+    };
+    Self { matcher, result, context_buffer: VecDeque::new() }
+  }
+}
+
+impl Sink for FileSink<'_> {
+  type Error = io::Error;
+
+  fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, io::Error> {
+    let text = String::from_utf8_lossy(mat.bytes()).to_string();
+    let mut submatches = Vec::new();
+    self
+      .matcher
+      .find_iter(mat.bytes(), |m| {
+        submatches.push(SubMatch { start: m.start(), end: m.end(), line_start: 0, line_end: 0 });
+        true
+      })
+      .map_err(io::Error::other)?;
+
+    let context_before: Vec<String> = self.context_buffer.drain(..).collect();
+    let context_before = context_before[context_before.len().saturating_sub(CONTEXT_LINES)..].to_vec();
+
+    self.result.total_matches += submatches.len();
+    self.result.matches.push(Match {
+      lines: Some(RipgrepLines { text: text.clone() }),
+      line_number: mat.line_number().unwrap_or_default() as usize,
+      context_before,
+      absolute_offset: mat.absolute_byte_offset() as usize,
+      submatches,
+      ..Default::default()
+    });
+    self.context_buffer.push_back(text);
+    Ok(true)
+  }
+
+  fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, io::Error> {
+    let text = String::from_utf8_lossy(context.bytes()).to_string();
+    let line_number = context.line_number().unwrap_or_default() as usize;
+
+    self.context_buffer.push_back(text.clone());
+    if self.context_buffer.len() > CONTEXT_LINES + 1 {
+      self.context_buffer.pop_front();
+    }
+    if let Some(last_match) = self.result.matches.last_mut() {
+      if line_number > last_match.line_number && last_match.context_after.len() < CONTEXT_LINES {
+        last_match.context_after.push(text);
+      }
+    }
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, sync::Mutex};
+
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_search() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let paths = vec![dir.clone()];
+    fs::write(dir.join("a.txt"), "one\nFoo two foo\nthree\n").unwrap();
+    fs::write(dir.join("b.txt"), "nothing here\n").unwrap();
+
+    let search_text_state = SearchTextState { text: "foo".to_string(), kind: SearchTextKind::Simple };
+    let results = Mutex::new(Vec::new());
+    let metadata =
+      search(&paths, &search_text_state, false, None, &|result| results.lock().unwrap().push(result)).unwrap();
+    let results = results.into_inner().unwrap();
+    assert_eq!(results.len(), 1);
+    let result = &results[0];
+    assert_eq!(result.total_matches, 2);
+    let found = &result.matches[0];
+    assert_eq!(found.line_number, 2);
+    assert_eq!(found.absolute_offset, 4);
+    assert_eq!(found.submatches.iter().map(|s| (s.start, s.end)).collect::<Vec<_>>(), vec![(0, 3), (8, 11)]);
+    assert_eq!(found.context_before, vec!["one\n"]);
+    assert_eq!(found.context_after, vec!["three\n"]);
+    assert_eq!((metadata.searches, metadata.searches_with_match, metadata.matches), (2, 1, 2));
+
+    let match_case = SearchTextState { text: "foo".to_string(), kind: SearchTextKind::MatchCase };
+    assert_eq!(search(&paths, &match_case, false, None, &|_| {}).unwrap().matches, 1);
+
+    fs::write(dir.join("b.txt"), b"caf\xe9 foo\n").unwrap();
+    let results = Mutex::new(Vec::new());
+    let latin1 = Some(encoding_rs::WINDOWS_1252);
+    search(&paths, &match_case, false, latin1, &|result| results.lock().unwrap().push(result)).unwrap();
+    let results = results.into_inner().unwrap();
+    let latin1_result = results.iter().find(|result| result.path.ends_with("b.txt")).unwrap();
+    let latin1_match = &latin1_result.matches[0];
+    let latin1_text = &latin1_match.lines.as_ref().unwrap().text;
+    assert_eq!(latin1_text, "café foo\n");
+    let submatch = &latin1_match.submatches[0];
+    assert_eq!(&latin1_text[submatch.start..submatch.end], "foo");
+
+    // Without an encoding the Latin-1 file isn't valid UTF-8 and is skipped, as a replace would skip it.
+    let results = Mutex::new(Vec::new());
+    search(&paths, &match_case, false, None, &|result| results.lock().unwrap().push(result)).unwrap();
+    let paths: Vec<String> = results.into_inner().unwrap().into_iter().map(|result| result.path).collect();
+    assert_eq!(paths, vec![dir.join("a.txt").to_string_lossy().to_string()]);
+  }
+}
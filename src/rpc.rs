@@ -0,0 +1,326 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
+
+use clap::ValueEnum;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+
+use crate::{
+  action::{AppAction, TuiAction},
+  cli::{ReplaceModeArg, SearchModeArg},
+  quickfix::result_entries,
+  redux::{
+    action::Action,
+    reducer::check_dialog_visible,
+    state::{ReplaceTextKind, SearchTextKind, State},
+    thunk::{ForceReplace, ThunkAction},
+  },
+};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The app ignores text typed while a dialog waits for an answer, the request is refused instead.
+const DIALOG_OPEN: i64 = -32000;
+
+/// Selection changes queued for a subscriber that reads slower than the user moves, older ones are dropped.
+const SELECTION_BACKLOG: usize = 16;
+
+/// Editor integration: JSON-RPC 2.0 on a Unix socket, one message per line. Requests become the same app actions
+/// key presses do, and subscribed clients are notified when the selected result changes. The socket is removed when
+/// the server is dropped.
+pub struct RpcServer {
+  path: PathBuf,
+  selections: broadcast::Sender<String>,
+  last_selection: Option<Selection>,
+  dialog_open: Arc<AtomicBool>,
+}
+
+/// Where the selected result's first match is, sent to subscribers as `selectionChanged`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+  pub path: String,
+  pub line: usize,
+  pub column: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct Request {
+  #[serde(default)]
+  id: Option<Value>,
+  method: String,
+  #[serde(default)]
+  params: Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct TextParams {
+  text: String,
+  kind: Option<String>,
+}
+
+/// What a request asks of the app.
+#[derive(Debug, PartialEq)]
+enum Call {
+  Actions(Vec<AppAction>),
+  Subscribe,
+}
+
+type RpcError = (i64, String);
+
+impl RpcServer {
+  #[cfg(unix)]
+  pub fn listen(path: &Path, command_tx: UnboundedSender<AppAction>) -> io::Result<Self> {
+    // A socket nobody answers on is left over from a run that didn't exit cleanly.
+    if path.exists() {
+      if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use", path.display())));
+      }
+      fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    let (selections, _) = broadcast::channel(SELECTION_BACKLOG);
+    let dialog_open = Arc::new(AtomicBool::new(false));
+
+    let accepted_selections = selections.clone();
+    let accepted_dialog_open = dialog_open.clone();
+    tokio::spawn(async move {
+      loop {
+        match listener.accept().await {
+          Ok((stream, _)) => {
+            tokio::spawn(serve(stream, command_tx.clone(), accepted_selections.clone(), accepted_dialog_open.clone()));
+          },
+          Err(e) => {
+            log::error!("Failed to accept an RPC connection: {}", e);
+            break;
+          },
+        }
+      }
+    });
+
+    Ok(Self { path: path.to_path_buf(), selections, last_selection: None, dialog_open })
+  }
+
+  #[cfg(not(unix))]
+  pub fn listen(_path: &Path, _command_tx: UnboundedSender<AppAction>) -> io::Result<Self> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the RPC socket needs Unix domain sockets"))
+  }
+
+  /// Notifies the subscribers when the selection differs from the one they were last told about, and keeps track of
+  /// whether a dialog is open for the requests that can't be served then.
+  pub fn publish_state(&mut self, state: &State) {
+    self.dialog_open.store(check_dialog_visible(state), Ordering::SeqCst);
+    let selection = Selection::from_state(state);
+    if selection == self.last_selection {
+      return;
+    }
+    self.last_selection = selection.clone();
+    if let Some(selection) = selection {
+      let notification = json!({ "jsonrpc": "2.0", "method": "selectionChanged", "params": selection });
+      let _ = self.selections.send(notification.to_string());
+    }
+  }
+}
+
+impl Drop for RpcServer {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+impl Selection {
+  /// The selected result's first match, its first line for a file path match. Paths are absolute for the editor,
+  /// which may run elsewhere.
+  fn from_state(state: &State) -> Option<Self> {
+    let result = &state.selected_result;
+    if result.path.is_empty() {
+      return None;
+    }
+    let path = fs::canonicalize(&result.path).map_or_else(|_| result.path.clone(), |p| p.to_string_lossy().to_string());
+    let (line, column) = result_entries(result, state).first().map_or((1, 1), |entry| (entry.line, entry.column));
+    Some(Self { path, line, column })
+  }
+}
+
+#[cfg(unix)]
+async fn serve(
+  stream: tokio::net::UnixStream,
+  command_tx: UnboundedSender<AppAction>,
+  selections: broadcast::Sender<String>,
+  dialog_open: Arc<AtomicBool>,
+) {
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+  let (reader, mut writer) = stream.into_split();
+  let mut lines = BufReader::new(reader).lines();
+  let mut subscription: Option<broadcast::Receiver<String>> = None;
+  loop {
+    let message = tokio::select! {
+      line = lines.next_line() => {
+        let Ok(Some(line)) = line else {
+          break;
+        };
+        let (response, subscribe) = handle_message(&line, &command_tx, dialog_open.load(Ordering::SeqCst));
+        if subscribe && subscription.is_none() {
+          subscription = Some(selections.subscribe());
+        }
+        match response {
+          Some(response) => response,
+          None => continue,
+        }
+      },
+      Some(notification) = next_notification(&mut subscription) => notification,
+    };
+    if writer.write_all(format!("{message}\n").as_bytes()).await.is_err() {
+      break;
+    }
+  }
+}
+
+/// The next selection change for a subscribed client, never resolving for the others.
+async fn next_notification(subscription: &mut Option<broadcast::Receiver<String>>) -> Option<String> {
+  let Some(receiver) = subscription else {
+    return std::future::pending().await;
+  };
+  loop {
+    match receiver.recv().await {
+      Ok(notification) => return Some(notification),
+      Err(broadcast::error::RecvError::Lagged(_)) => continue,
+      Err(broadcast::error::RecvError::Closed) => return None,
+    }
+  }
+}
+
+/// Runs one message, returning the response to send, None for a notification, and whether the client subscribed.
+fn handle_message(message: &str, command_tx: &UnboundedSender<AppAction>, dialog_open: bool) -> (Option<String>, bool) {
+  if message.trim().is_empty() {
+    return (None, false);
+  }
+  let request: Request = match serde_json::from_str(message) {
+    Ok(request) => request,
+    Err(e) => return (Some(error_response(Value::Null, (PARSE_ERROR, e.to_string()))), false),
+  };
+
+  let outcome = call(&request.method, request.params, dialog_open);
+  let subscribe = outcome == Ok(Call::Subscribe);
+  if let Ok(Call::Actions(actions)) = &outcome {
+    for action in actions {
+      let _ = command_tx.send(action.clone());
+    }
+  }
+
+  let response = request.id.map(|id| {
+    match outcome {
+      Ok(_) => json!({ "jsonrpc": "2.0", "id": id, "result": null }).to_string(),
+      Err(error) => error_response(id, error),
+    }
+  });
+  (response, subscribe)
+}
+
+fn error_response(id: Value, (code, message): RpcError) -> String {
+  json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }).to_string()
+}
+
+fn call(method: &str, params: Value, dialog_open: bool) -> Result<Call, RpcError> {
+  match method {
+    "setSearchText" | "setReplaceText" if dialog_open => {
+      Err((DIALOG_OPEN, "a dialog is open, answer it in serpl first".to_string()))
+    },
+    "setSearchText" => {
+      let TextParams { text, kind } = parse_params(params)?;
+      let mut actions = vec![
+        AppAction::Tui(TuiAction::SetSearchInput(text.clone())),
+        AppAction::Action(Action::SetSearchText { text }),
+      ];
+      if let Some(kind) = kind {
+        let kind = SearchTextKind::from(parse_kind::<SearchModeArg>(&kind)?);
+        actions.push(AppAction::Action(Action::SetSearchTextKind { kind }));
+        #[cfg(feature = "ast_grep")]
+        if kind == SearchTextKind::AstGrep {
+          actions.push(AppAction::Action(Action::SetReplaceTextKind { kind: ReplaceTextKind::AstGrep }));
+        }
+      }
+      Ok(Call::Actions(actions))
+    },
+    "setReplaceText" => {
+      let TextParams { text, kind } = parse_params(params)?;
+      let mut actions = vec![
+        AppAction::Tui(TuiAction::SetReplaceInput(text.clone())),
+        AppAction::Action(Action::SetReplaceText { text }),
+      ];
+      if let Some(kind) = kind {
+        let kind = ReplaceTextKind::from(parse_kind::<ReplaceModeArg>(&kind)?);
+        actions.push(AppAction::Action(Action::SetReplaceTextKind { kind }));
+      }
+      Ok(Call::Actions(actions))
+    },
+    "search" => Ok(Call::Actions(vec![AppAction::Thunk(ThunkAction::ProcessSearch)])),
+    "replace" => Ok(Call::Actions(vec![AppAction::Thunk(ThunkAction::ProcessReplace(ForceReplace(false)))])),
+    "subscribe" => Ok(Call::Subscribe),
+    _ => Err((METHOD_NOT_FOUND, format!("unknown method {method}"))),
+  }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+  serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+/// A mode named like its command line value, e.g. `match-case`.
+fn parse_kind<T: ValueEnum>(kind: &str) -> Result<T, RpcError> {
+  T::from_str(kind, true).map_err(|_| (INVALID_PARAMS, format!("unknown kind {kind}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+  use tokio::sync::mpsc;
+
+  use super::*;
+
+  #[test]
+  fn test_handle_message() {
+    let (command_tx, mut command_rx) = mpsc::unbounded_channel();
+
+    let request = r#"{"jsonrpc":"2.0","id":1,"method":"setSearchText","params":{"text":"foo","kind":"match-case"}}"#;
+    let (response, subscribe) = handle_message(request, &command_tx, false);
+    assert_eq!(response.unwrap(), r#"{"id":1,"jsonrpc":"2.0","result":null}"#);
+    assert!(!subscribe);
+    assert_eq!(command_rx.try_recv().unwrap(), AppAction::Tui(TuiAction::SetSearchInput("foo".to_string())));
+    assert_eq!(command_rx.try_recv().unwrap(), AppAction::Action(Action::SetSearchText { text: "foo".to_string() }));
+    assert_eq!(
+      command_rx.try_recv().unwrap(),
+      AppAction::Action(Action::SetSearchTextKind { kind: SearchTextKind::MatchCase })
+    );
+
+    let (response, _) = handle_message(r#"{"jsonrpc":"2.0","method":"search"}"#, &command_tx, false);
+    assert_eq!(response, None);
+    assert_eq!(command_rx.try_recv().unwrap(), AppAction::Thunk(ThunkAction::ProcessSearch));
+
+    let (response, subscribe) = handle_message(r#"{"jsonrpc":"2.0","id":2,"method":"subscribe"}"#, &command_tx, false);
+    assert_eq!(response.unwrap(), r#"{"id":2,"jsonrpc":"2.0","result":null}"#);
+    assert!(subscribe);
+
+    let (response, _) = handle_message(r#"{"jsonrpc":"2.0","id":3,"method":"quit"}"#, &command_tx, false);
+    assert_eq!(
+      response.unwrap(),
+      r#"{"error":{"code":-32601,"message":"unknown method quit"},"id":3,"jsonrpc":"2.0"}"#
+    );
+    assert!(command_rx.try_recv().is_err());
+
+    let request = r#"{"jsonrpc":"2.0","id":4,"method":"setSearchText","params":{"text":"bar"}}"#;
+    let (response, _) = handle_message(request, &command_tx, true);
+    assert_eq!(
+      response.unwrap(),
+      r#"{"error":{"code":-32000,"message":"a dialog is open, answer it in serpl first"},"id":4,"jsonrpc":"2.0"}"#
+    );
+    assert!(command_rx.try_recv().is_err());
+  }
+}
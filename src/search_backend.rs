@@ -0,0 +1,78 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use color_eyre::eyre::Result;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::redux::state::{
+  AstGrepState, Metadata, ReplaceTextState, SearchResultState, SearchTextKind, SearchTextState,
+};
+
+#[cfg(feature = "ast_grep")]
+pub mod ast_grep;
+pub mod native;
+pub mod ripgrep;
+
+/// Everything a backend needs to run one search.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+  pub paths: Vec<PathBuf>,
+  pub search_text: SearchTextState,
+  pub replace_text: ReplaceTextState,
+  pub ast_grep: AstGrepState,
+  /// Encoding of files without a byte order mark, UTF-8 when None.
+  pub encoding: Option<&'static Encoding>,
+}
+
+/// An engine producing content search results.
+pub trait SearchBackend: Send + Sync {
+  /// Whether the backend can run searches of this kind.
+  fn supports(&self, kind: SearchTextKind) -> bool;
+
+  /// Runs the search, sending each file's results as soon as they are complete.
+  fn search(&self, query: &SearchQuery, results: &UnboundedSender<SearchResultState>) -> Result<Metadata>;
+}
+
+/// A command registered under `search_backends` in the config file, printing ripgrep compatible JSON.
+/// `{pattern}` in the arguments stands for the search text, which is appended when no argument contains it.
+/// The search paths are always appended last.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExternalSearchBackend {
+  pub command: String,
+  #[serde(default)]
+  pub args: Vec<String>,
+}
+
+/// The configured backend named `search_engine`, None when it is the built-in one, unknown or can't run searches of
+/// `kind`.
+pub fn configured_backend(
+  search_engine: &str,
+  external_backends: &HashMap<String, ExternalSearchBackend>,
+  kind: SearchTextKind,
+) -> Option<Box<dyn SearchBackend>> {
+  let selected: Option<Box<dyn SearchBackend>> = match search_engine {
+    "" | "native" => None,
+    "ripgrep" => Some(Box::new(ripgrep::RipgrepBackend::default())),
+    name => {
+      match external_backends.get(name) {
+        Some(external) => Some(Box::new(ripgrep::RipgrepBackend::external(external))),
+        None => {
+          log::warn!("Unknown search engine {name}, using the built-in one");
+          None
+        },
+      }
+    },
+  };
+
+  selected.filter(|backend| backend.supports(kind))
+}
+
+/// The built-in backend for searches of `kind`.
+pub fn default_backend(kind: SearchTextKind) -> Box<dyn SearchBackend> {
+  #[cfg(feature = "ast_grep")]
+  if kind == SearchTextKind::AstGrep {
+    return Box::new(ast_grep::AstGrepBackend);
+  }
+  Box::new(native::NativeBackend)
+}
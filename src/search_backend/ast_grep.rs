@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fs};
+
+use color_eyre::eyre::Result;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{SearchBackend, SearchQuery};
+use crate::{
+  redux::{
+    state::{Match, Metadata, SearchResultKind, SearchResultState, SearchTextKind, SubMatch},
+    utils::{content_hash, run_ast_grep},
+  },
+  ripgrep::RipgrepLines,
+};
+
+/// Structural search with ast-grep, in process or through the binary depending on the build.
+pub struct AstGrepBackend;
+
+impl AstGrepBackend {
+  fn get_context(lines: &[&str], start: usize, count: usize, forward: bool) -> Vec<String> {
+    let mut context = Vec::new();
+    let mut current = start;
+
+    for _ in 0..count {
+      if forward {
+        if current >= lines.len() {
+          break;
+        }
+        context.push(lines[current].to_string());
+        current += 1;
+      } else {
+        if current == 0 {
+          break;
+        }
+        current -= 1;
+        context.insert(0, lines[current].to_string());
+      }
+    }
+
+    context
+  }
+}
+
+impl SearchBackend for AstGrepBackend {
+  fn supports(&self, kind: SearchTextKind) -> bool {
+    kind == SearchTextKind::AstGrep
+  }
+
+  fn search(&self, query: &SearchQuery, results: &UnboundedSender<SearchResultState>) -> Result<Metadata> {
+    let replace_text = &query.replace_text.text;
+    let replacement = (!replace_text.is_empty()).then_some(replace_text.as_str());
+    let ast_grep_results = run_ast_grep(&query.paths, &query.search_text.text, replacement, &query.ast_grep)?;
+
+    let mut aggregated_results: HashMap<String, SearchResultState> = HashMap::new();
+    for result in ast_grep_results {
+      let file_content = fs::read_to_string(&result.file).unwrap_or_default();
+      let lines: Vec<&str> = file_content.lines().collect();
+
+      let context_before = Self::get_context(&lines, result.range.start.line, 3, false);
+      let context_after = Self::get_context(&lines, result.range.end.line, 3, true);
+
+      aggregated_results
+        .entry(result.file.clone())
+        .or_insert_with(|| {
+          SearchResultState {
+            index: None,
+            path: result.file.clone(),
+            matches: Vec::new(),
+            total_matches: 0,
+            kind: SearchResultKind::Content,
+            content_hash: Some(content_hash(&file_content)),
+            removed: false,
+          }
+        })
+        .matches
+        .push(Match {
+          line_number: result.range.start.line,
+          lines: Some(RipgrepLines { text: result.lines }),
+          absolute_offset: result.range.byte_offset.start,
+          submatches: vec![SubMatch {
+            start: result.range.start.column,
+            end: result.range.end.column,
+            line_start: result.range.start.line,
+            line_end: result.range.end.line,
+          }],
+          replacement: result.replacement,
+          replacement_offsets: result.replacement_offsets,
+          byte_range: Some(result.range.byte_offset.start..result.range.byte_offset.end),
+          context_before,
+          context_after,
+          meta_variables: result.meta_variables.as_ref().map(|variables| variables.bindings()).unwrap_or_default(),
+        });
+    }
+
+    let mut metadata = Metadata { searches: 1, ..Default::default() };
+    for (_, mut result) in aggregated_results {
+      result.total_matches = result.matches.len();
+      metadata.matched_lines += result.total_matches;
+      metadata.matches += result.total_matches;
+      metadata.searches_with_match = 1;
+      let _ = results.send(result);
+    }
+    Ok(metadata)
+  }
+}
@@ -0,0 +1,30 @@
+use color_eyre::eyre::Result;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{SearchBackend, SearchQuery};
+use crate::{
+  redux::state::{Metadata, ReplaceTextKind, SearchResultState, SearchTextKind},
+  ripgrep,
+};
+
+/// In process text search on ripgrep's library crates.
+pub struct NativeBackend;
+
+impl SearchBackend for NativeBackend {
+  fn supports(&self, kind: SearchTextKind) -> bool {
+    #[cfg(feature = "ast_grep")]
+    if kind == SearchTextKind::AstGrep {
+      return false;
+    }
+    true
+  }
+
+  fn search(&self, query: &SearchQuery, results: &UnboundedSender<SearchResultState>) -> Result<Metadata> {
+    let is_smart_case = query.replace_text.kind == ReplaceTextKind::SmartCase;
+    let metadata =
+      ripgrep::native::search(&query.paths, &query.search_text, is_smart_case, query.encoding, &|result| {
+        let _ = results.send(result);
+      })?;
+    Ok(metadata)
+  }
+}
@@ -0,0 +1,258 @@
+use std::{
+  collections::VecDeque,
+  io::{BufRead, BufReader, Read},
+  process::{Command, Stdio},
+  thread,
+};
+
+use color_eyre::eyre::{eyre, Result};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{ExternalSearchBackend, SearchBackend, SearchQuery};
+use crate::{
+  redux::{
+    state::{Match, Metadata, ReplaceTextKind, SearchResultKind, SearchResultState, SearchTextKind, SubMatch},
+    utils::smart_case_pattern,
+  },
+  ripgrep::RipgrepOutput,
+};
+
+/// Runs a command printing ripgrep's JSON output, by default `rg` itself.
+pub struct RipgrepBackend {
+  command: String,
+  /// Arguments of a user configured command, `None` passes `rg` the flags of the search mode.
+  args: Option<Vec<String>>,
+}
+
+impl Default for RipgrepBackend {
+  fn default() -> Self {
+    Self { command: "rg".to_string(), args: None }
+  }
+}
+
+impl RipgrepBackend {
+  pub fn external(backend: &ExternalSearchBackend) -> Self {
+    Self { command: backend.command.clone(), args: Some(backend.args.clone()) }
+  }
+
+  fn command_args(&self, query: &SearchQuery) -> Vec<String> {
+    let is_smart_case = query.replace_text.kind == ReplaceTextKind::SmartCase;
+    let pattern =
+      if is_smart_case { smart_case_pattern(&query.search_text.text) } else { query.search_text.text.clone() };
+
+    if let Some(args) = &self.args {
+      let mut command_args: Vec<String> = args.iter().map(|arg| arg.replace("{pattern}", &pattern)).collect();
+      if !args.iter().any(|arg| arg.contains("{pattern}")) {
+        command_args.push(pattern);
+      }
+      return command_args;
+    }
+
+    let mut rg_args = vec!["--json", "-C", "3"];
+    if let Some(encoding) = query.encoding {
+      rg_args.extend(["-E", encoding.name()]);
+    }
+    match query.search_text.kind {
+      SearchTextKind::Regex => rg_args.push(&query.search_text.text),
+      SearchTextKind::MatchCase => rg_args.extend(["-s", &pattern]),
+      SearchTextKind::MatchWholeWord => rg_args.extend(["-w", "-i", &pattern]),
+      SearchTextKind::MatchCaseWholeWord => rg_args.extend(["-w", "-s", &pattern]),
+      SearchTextKind::Simple if is_smart_case => rg_args.extend(["-i", &pattern]),
+      SearchTextKind::Simple => rg_args.extend(["-i", "-F", &pattern]),
+      #[cfg(feature = "ast_grep")]
+      SearchTextKind::AstGrep => {},
+    }
+    rg_args.into_iter().map(String::from).collect()
+  }
+}
+
+impl SearchBackend for RipgrepBackend {
+  fn supports(&self, kind: SearchTextKind) -> bool {
+    #[cfg(feature = "ast_grep")]
+    if kind == SearchTextKind::AstGrep {
+      return false;
+    }
+    true
+  }
+
+  fn search(&self, query: &SearchQuery, results: &UnboundedSender<SearchResultState>) -> Result<Metadata> {
+    let mut child = Command::new(&self.command)
+      .args(self.command_args(query))
+      .args(&query.paths)
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped())
+      .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    // Read on its own thread so a chatty command can't block on a full stderr pipe while stdout is parsed.
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = thread::spawn(move || {
+      let mut errors = String::new();
+      let _ = stderr.read_to_string(&mut errors);
+      errors
+    });
+
+    let mut current: Option<SearchResultState> = None;
+    let mut metadata = Metadata::default();
+    let mut context_buffer: VecDeque<(usize, String)> = VecDeque::new();
+
+    for line in BufReader::new(stdout).lines() {
+      let Ok(rg_output) = serde_json::from_str::<RipgrepOutput>(&line?) else {
+        continue;
+      };
+      match rg_output.kind.as_str() {
+        "match" | "context" => {
+          let Some(data) = rg_output.data else {
+            continue;
+          };
+          let path = data.path.unwrap().text;
+          let line_number = data.line_number.unwrap_or_default() as usize;
+          let absolute_offset = data.absolute_offset.unwrap_or_default();
+
+          if current.as_ref().is_some_and(|result| result.path != path) {
+            send_result(current.take(), results);
+          }
+          let result = current.get_or_insert_with(|| {
+            SearchResultState { path: path.clone(), kind: SearchResultKind::Content, ..Default::default() }
+          });
+
+          if rg_output.kind == "match" {
+            let submatches: Vec<SubMatch> = data
+              .submatches
+              .unwrap_or_default()
+              .into_iter()
+              .map(|sm| SubMatch { start: sm.start as usize, end: sm.end as usize, line_start: 0, line_end: 0 })
+              .collect();
+
+            let mut context_before: Vec<String> = context_buffer.drain(..).map(|(_, line)| line).collect();
+            if context_before.len() > 3 {
+              context_before = context_before.clone().into_iter().skip(context_before.len() - 3).collect();
+            }
+
+            result.total_matches += submatches.len();
+            result.matches.push(Match {
+              lines: data.lines.clone(),
+              line_number,
+              context_before,
+              context_after: Vec::new(),
+              absolute_offset: absolute_offset as usize,
+              submatches,
+              ..Default::default()
+            });
+
+            context_buffer.push_back((line_number, data.lines.unwrap().text));
+          } else {
+            context_buffer.push_back((line_number, data.lines.clone().unwrap().text));
+            if context_buffer.len() > 4 {
+              context_buffer.pop_front();
+            }
+
+            if let Some(last_match) = result.matches.last_mut() {
+              if line_number > last_match.line_number && last_match.context_after.len() < 3 {
+                last_match.context_after.push(data.lines.unwrap().text);
+              }
+            }
+          }
+        },
+        "end" => {
+          send_result(current.take(), results);
+          context_buffer.clear();
+        },
+        "summary" => {
+          if let Some(data) = rg_output.data {
+            let stats = data.stats.as_ref().unwrap();
+            metadata = Metadata {
+              elapsed_time: data.elapsed_total.unwrap().nanos,
+              matched_lines: stats.matched_lines,
+              matches: stats.matches,
+              searches: stats.searches,
+              searches_with_match: stats.searches_with_match,
+            };
+          }
+        },
+        _ => {},
+      }
+    }
+
+    send_result(current, results);
+    let status = child.wait()?;
+    let errors = stderr_reader.join().unwrap_or_default();
+    // Like grep, ripgrep exits with 1 when nothing matched.
+    if !status.success() && status.code() != Some(1) {
+      let errors = errors.trim();
+      return Err(if errors.is_empty() { eyre!("{} exited with {}", self.command, status) } else { eyre!("{errors}") });
+    }
+    Ok(metadata)
+  }
+}
+
+fn send_result(result: Option<SearchResultState>, results: &UnboundedSender<SearchResultState>) {
+  if let Some(result) = result.filter(|result| !result.matches.is_empty()) {
+    let _ = results.send(result);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::redux::state::SearchTextState;
+
+  #[test]
+  fn test_command_args() {
+    let query = SearchQuery {
+      paths: vec![],
+      search_text: SearchTextState { text: "foo".to_string(), kind: SearchTextKind::MatchCase },
+      replace_text: Default::default(),
+      ast_grep: Default::default(),
+      encoding: None,
+    };
+    assert_eq!(RipgrepBackend::default().command_args(&query), vec!["--json", "-C", "3", "-s", "foo"]);
+    let latin1_query = SearchQuery { encoding: Some(encoding_rs::WINDOWS_1252), ..query.clone() };
+    assert_eq!(RipgrepBackend::default().command_args(&latin1_query), vec![
+      "--json",
+      "-C",
+      "3",
+      "-E",
+      "windows-1252",
+      "-s",
+      "foo"
+    ]);
+
+    let external = |args: &[&str]| {
+      RipgrepBackend::external(&ExternalSearchBackend {
+        command: "rg".to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+      })
+    };
+    assert_eq!(external(&["--json", "--hidden"]).command_args(&query), vec!["--json", "--hidden", "foo"]);
+    assert_eq!(external(&["--json", "-e", "{pattern}", "--hidden"]).command_args(&query), vec![
+      "--json", "-e", "foo", "--hidden"
+    ]);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_search_reports_failures() {
+    let query = SearchQuery {
+      paths: vec![],
+      search_text: SearchTextState { text: "foo(".to_string(), kind: SearchTextKind::Regex },
+      replace_text: Default::default(),
+      ast_grep: Default::default(),
+      encoding: None,
+    };
+    let shell = |script: &str| {
+      RipgrepBackend::external(&ExternalSearchBackend {
+        command: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+      })
+    };
+    let (results_tx, _results_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let error = shell("echo 'regex parse error' >&2; exit 2").search(&query, &results_tx).unwrap_err();
+    assert_eq!(error.to_string(), "regex parse error");
+    let error = shell("exit 3").search(&query, &results_tx).unwrap_err();
+    assert_eq!(error.to_string(), "sh exited with exit status: 3");
+    assert_eq!(shell("exit 1").search(&query, &results_tx).unwrap(), Metadata::default());
+  }
+}
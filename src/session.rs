@@ -0,0 +1,135 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  change_set::file_hash,
+  redux::state::{Exclusions, GitScope, ReplaceTextState, SearchResultState, SearchTextState, State},
+  utils::get_data_dir,
+};
+
+/// What `--resume` brings back of the last run in the same project roots: the query, its modes and filters, the
+/// results removed with `d`, and the selected file.
+#[derive(Default, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Session {
+  /// The absolute project roots, which the file name only holds a hash of.
+  #[serde(default)]
+  pub project_roots: Vec<PathBuf>,
+  pub search_text: SearchTextState,
+  pub replace_text: ReplaceTextState,
+  pub include_file_paths: bool,
+  pub ast_grep_lang: Option<String>,
+  pub scope: GitScope,
+  pub exclusions: Exclusions,
+  pub selected_path: Option<String>,
+}
+
+impl Session {
+  pub fn from_state(state: &State) -> Self {
+    Self {
+      project_roots: absolute_roots(&state.project_roots),
+      search_text: state.search_text.clone(),
+      replace_text: state.replace_text.clone(),
+      include_file_paths: state.include_file_paths,
+      ast_grep_lang: state.ast_grep.lang.clone(),
+      scope: state.git.scope.clone(),
+      exclusions: state.exclusions.clone(),
+      selected_path: Some(state.selected_result.path.clone()).filter(|path| !path.is_empty()),
+    }
+  }
+
+  /// Puts the session back into a fresh state. Options given on the command line, a language or a git scope, win
+  /// over the saved ones. The selected file is selected again once the search finds it.
+  pub fn restore(self, state: State) -> State {
+    let mut state = State {
+      search_text: self.search_text,
+      replace_text: self.replace_text,
+      include_file_paths: self.include_file_paths,
+      exclusions: self.exclusions,
+      selected_result: SearchResultState { path: self.selected_path.unwrap_or_default(), ..Default::default() },
+      ..state
+    };
+    if state.ast_grep.lang.is_none() {
+      state.ast_grep.lang = self.ast_grep_lang;
+    }
+    if state.git.scope == GitScope::All {
+      state.git.scope = self.scope;
+    }
+    state
+  }
+
+  pub fn save(&self, project_roots: &[PathBuf]) -> io::Result<()> {
+    let path = session_file(project_roots);
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(self)?)
+  }
+
+  /// The session saved for the project roots, None when there is none.
+  pub fn load(project_roots: &[PathBuf]) -> io::Result<Option<Self>> {
+    match fs::read_to_string(session_file(project_roots)) {
+      Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e),
+    }
+  }
+}
+
+fn session_file(project_roots: &[PathBuf]) -> PathBuf {
+  get_data_dir().join("sessions").join(format!("{}.json", session_name(project_roots)))
+}
+
+/// File name standing for the absolute project roots. A hash, since the escaped roots could exceed the length file
+/// systems allow for a name.
+fn session_name(project_roots: &[PathBuf]) -> String {
+  let roots: Vec<String> =
+    absolute_roots(project_roots).iter().map(|root| root.to_string_lossy().to_string()).collect();
+  file_hash(roots.join("\0").as_bytes())
+}
+
+fn absolute_roots(project_roots: &[PathBuf]) -> Vec<PathBuf> {
+  project_roots.iter().map(|root| root.canonicalize().unwrap_or_else(|_| root.to_path_buf())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+  use crate::redux::state::{ExcludedLine, SearchTextKind};
+
+  #[test]
+  fn test_session_name() {
+    let roots = [PathBuf::from("/serpl-missing/a"), PathBuf::from("/serpl-missing/b")];
+    let name = session_name(&roots);
+    assert_eq!(name.len(), 16);
+    assert_eq!(session_name(&roots), name);
+    assert_ne!(session_name(&[roots[1].clone(), roots[0].clone()]), name);
+
+    let long_root = PathBuf::from(format!("/serpl-missing/{}", "a".repeat(300)));
+    assert_eq!(session_name(&[long_root]).len(), 16);
+  }
+
+  #[test]
+  fn test_restore() {
+    let state = State {
+      search_text: SearchTextState { text: "foo".to_string(), kind: SearchTextKind::Regex },
+      exclusions: Exclusions {
+        files: vec!["a.rs".to_string()],
+        lines: vec![ExcludedLine { path: "b.rs".to_string(), line_number: 3 }],
+      },
+      selected_result: SearchResultState { path: "c.rs".to_string(), index: Some(2), ..Default::default() },
+      ..Default::default()
+    };
+    let session: Session = serde_json::from_str(&serde_json::to_string(&Session::from_state(&state)).unwrap()).unwrap();
+
+    let restored = session.restore(State::default());
+    assert_eq!(restored.search_text, state.search_text);
+    assert_eq!(restored.exclusions, state.exclusions);
+    assert_eq!(restored.selected_result, SearchResultState { path: "c.rs".to_string(), ..Default::default() });
+  }
+}
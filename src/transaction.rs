@@ -0,0 +1,174 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+/// New contents for a set of files, written all together or not at all. Each content is staged in a temporary file
+/// next to its target and renamed into place only once every file is staged, a failing rename restores the files
+/// already replaced from hard link backups.
+#[derive(Default, Debug)]
+pub struct FileTransaction {
+  writes: Vec<(PathBuf, Vec<u8>)>,
+}
+
+/// A file whose content was moved into place, with the backup of its previous content.
+struct Replaced {
+  path: PathBuf,
+  backup: PathBuf,
+}
+
+impl FileTransaction {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn stage(&mut self, path: impl Into<PathBuf>, content: Vec<u8>) {
+    self.writes.push((path.into(), content));
+  }
+
+  pub fn len(&self) -> usize {
+    self.writes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.writes.is_empty()
+  }
+
+  /// Writes every staged content, leaving all files untouched when any of them can't be written. The error carries
+  /// the file that failed.
+  pub fn commit(self) -> Result<(), (PathBuf, io::Error)> {
+    self.commit_each(|_, _| {})
+  }
+
+  /// Like `commit`, calling `on_renamed` with each file and the length of its new content once it is in place.
+  pub fn commit_each(self, mut on_renamed: impl FnMut(&Path, usize)) -> Result<(), (PathBuf, io::Error)> {
+    for (path, _) in &self.writes {
+      let metadata = fs::metadata(path).map_err(|e| (path.clone(), e))?;
+      if metadata.permissions().readonly() {
+        return Err((path.clone(), io::Error::new(io::ErrorKind::PermissionDenied, "file is read-only")));
+      }
+    }
+
+    let mut temp_files = Vec::new();
+    for (path, content) in &self.writes {
+      let temp_file = sibling(path, "tmp");
+      let staged = fs::write(&temp_file, content)
+        .and_then(|_| fs::set_permissions(&temp_file, fs::metadata(path)?.permissions()))
+        .map_err(|e| (path.clone(), e));
+      temp_files.push(temp_file);
+      if let Err(e) = staged {
+        remove_all(&temp_files);
+        return Err(e);
+      }
+    }
+
+    let mut replaced: Vec<Replaced> = Vec::new();
+    for ((path, content), temp_file) in self.writes.iter().zip(&temp_files) {
+      let backup = sibling(path, "bak");
+      let result = backup_file(path, &backup).and_then(|_| {
+        fs::rename(temp_file, path).inspect_err(|_| {
+          let _ = fs::remove_file(&backup);
+        })
+      });
+      match result {
+        Ok(()) => {
+          replaced.push(Replaced { path: path.clone(), backup });
+          on_renamed(path, content.len());
+        },
+        Err(e) => {
+          rollback(&replaced);
+          remove_all(&temp_files);
+          return Err((path.clone(), e));
+        },
+      }
+    }
+
+    remove_all(&replaced.iter().map(|replaced| replaced.backup.clone()).collect::<Vec<_>>());
+    Ok(())
+  }
+}
+
+/// Hidden file next to `path`, named after it so a leftover from a crash is easy to trace back.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+  let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+  path.with_file_name(format!(".{name}.serpl-{}.{extension}", std::process::id()))
+}
+
+/// Keeps the current content of `path` reachable under `backup`, a hard link where the file system allows it.
+fn backup_file(path: &Path, backup: &Path) -> io::Result<()> {
+  fs::hard_link(path, backup).or_else(|_| fs::copy(path, backup).map(|_| ()))
+}
+
+fn rollback(replaced: &[Replaced]) {
+  for replaced in replaced.iter().rev() {
+    if let Err(e) = fs::rename(&replaced.backup, &replaced.path) {
+      log::error!("Failed to restore {} from {}: {}", replaced.path.display(), replaced.backup.display(), e);
+    }
+  }
+}
+
+fn remove_all(paths: &[PathBuf]) {
+  for path in paths {
+    let _ = fs::remove_file(path);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_commit() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    fs::write(&first, "one\n").unwrap();
+    fs::write(&second, "two\n").unwrap();
+
+    let mut transaction = FileTransaction::new();
+    transaction.stage(&first, b"uno\n".to_vec());
+    transaction.stage(&second, b"dos\n".to_vec());
+    transaction.stage(dir.join("missing.txt"), b"tres\n".to_vec());
+    assert_eq!(transaction.commit().unwrap_err().0, dir.join("missing.txt"));
+    assert_eq!(fs::read_to_string(&first).unwrap(), "one\n");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "two\n");
+
+    let mut transaction = FileTransaction::new();
+    transaction.stage(&first, b"uno\n".to_vec());
+    transaction.stage(&second, b"dos\n".to_vec());
+    transaction.commit().unwrap();
+    assert_eq!(fs::read_to_string(&first).unwrap(), "uno\n");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "dos\n");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+  }
+
+  #[test]
+  fn test_commit_rolls_back() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    let first = dir.join("first.txt");
+    let second = dir.join("second.txt");
+    fs::write(&first, "one\n").unwrap();
+    fs::write(&second, "two\n").unwrap();
+    // Passes the checks and stages fine, but can't be moved into place once the first file was.
+    let directory = dir.join("directory");
+    fs::create_dir(&directory).unwrap();
+
+    let mut transaction = FileTransaction::new();
+    transaction.stage(&first, b"uno\n".to_vec());
+    transaction.stage(&directory, b"dos\n".to_vec());
+    transaction.stage(&second, b"tres\n".to_vec());
+    assert_eq!(transaction.commit().unwrap_err().0, directory);
+
+    assert_eq!(fs::read_to_string(&first).unwrap(), "one\n");
+    assert_eq!(fs::read_to_string(&second).unwrap(), "two\n");
+    assert!(directory.is_dir());
+    let mut names: Vec<String> =
+      fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+    names.sort();
+    assert_eq!(names, vec!["directory", "first.txt", "second.txt"]);
+  }
+}
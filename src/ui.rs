@@ -1,3 +1,4 @@
+pub mod choice_dialog_widget;
 pub mod confirm_dialog_widget;
 pub mod divider;
 pub mod help_display_dialog;
@@ -0,0 +1,105 @@
+use ratatui::{
+  buffer::Buffer,
+  layout::{Alignment, Constraint, Direction, Layout, Rect},
+  style::{Color, Modifier, Style, Stylize},
+  text::Text,
+  widgets::{Block, BorderType, Borders, Clear, Padding, Paragraph, StatefulWidget, Widget, Wrap},
+};
+
+use crate::utils::centered_rect_with_size;
+
+/// A dialog offering several choices as a row of buttons, for decisions that are more than confirm or cancel.
+#[derive(Default, Debug, Clone)]
+pub struct ChoiceDialogWidget {
+  pub title: String,
+  pub message: String,
+  pub choices: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct ChoiceDialogState {
+  pub selected: usize,
+}
+
+impl ChoiceDialogState {
+  pub fn next(&mut self, choice_count: usize) {
+    self.selected = (self.selected + 1) % choice_count.max(1);
+  }
+
+  pub fn previous(&mut self, choice_count: usize) {
+    self.selected = (self.selected + choice_count.max(1) - 1) % choice_count.max(1);
+  }
+}
+
+impl ChoiceDialogWidget {
+  pub fn new(title: String, message: String, choices: Vec<String>) -> Self {
+    Self { title, message, choices }
+  }
+}
+
+impl StatefulWidget for ChoiceDialogWidget {
+  type State = ChoiceDialogState;
+
+  fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+    let horizontal_padding = 2u16;
+    let vertical_padding = 2u16;
+    let buttons_padding = 2u16;
+
+    let block = Block::default()
+      .title(self.title)
+      .title_alignment(Alignment::Center)
+      .borders(Borders::ALL)
+      .border_type(BorderType::Rounded)
+      .border_style(Style::default().fg(Color::Yellow));
+
+    let button_sizes: Vec<u16> =
+      self.choices.iter().map(|choice| (choice.len() + buttons_padding as usize) as u16).collect();
+    let buttons_width: u16 = button_sizes.iter().sum();
+
+    let text = self.message;
+    let width = (text.lines().map(|line| line.len()).max().unwrap_or(0) as u16 + 4).max(buttons_width + 4);
+    let height = text.lines().count() as u16 + 6;
+
+    let text_widget = Paragraph::new(Text::from(text))
+      .block(Block::new().padding(Padding::new(
+        horizontal_padding,
+        horizontal_padding,
+        vertical_padding,
+        vertical_padding,
+      )))
+      .alignment(Alignment::Center)
+      .style(Style::new().white())
+      .wrap(Wrap { trim: true });
+
+    let centered_area = centered_rect_with_size(width, height, area);
+
+    let main_layout = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Min(1), Constraint::Max(2)])
+      .split(centered_area);
+
+    Clear.render(centered_area, buf);
+    text_widget.render(main_layout[0], buf);
+    block.render(centered_area, buf);
+
+    let margin = main_layout[1].width.saturating_sub(buttons_width) / 2;
+    let mut constraints = vec![Constraint::Length(margin)];
+    constraints.extend(button_sizes.iter().map(|size| Constraint::Max(*size)));
+    constraints.push(Constraint::Length(margin));
+    let buttons_layout =
+      Layout::default().direction(Direction::Horizontal).constraints(constraints).split(main_layout[1]);
+
+    for (index, choice) in self.choices.iter().enumerate() {
+      let style = if index == state.selected {
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+      } else {
+        Style::default().fg(Color::White)
+      };
+      Paragraph::new(choice.as_str())
+        .style(style)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::BOTTOM).border_style(Style::default().fg(Color::Yellow)))
+        .render(buttons_layout[index + 1], buf);
+    }
+  }
+}
@@ -0,0 +1,114 @@
+use std::{
+  collections::BTreeSet,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use ignore::gitignore::Gitignore;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::{action::AppAction, redux::thunk::ThunkAction};
+
+/// Quiet time after a change before the changed files are searched again, so a save through a temporary file or a
+/// `git checkout` touching many files is refreshed once.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+/// A project root as given on the command line, with the absolute path the watcher reports changes under.
+struct WatchedRoot {
+  root: PathBuf,
+  absolute: PathBuf,
+  gitignore: Gitignore,
+}
+
+impl WatchedRoot {
+  fn new(root: &Path) -> std::io::Result<Self> {
+    let absolute = root.canonicalize()?;
+    // A missing .gitignore leaves the matcher empty, which is all the error says.
+    let (gitignore, _) = Gitignore::new(absolute.join(".gitignore"));
+    Ok(Self { root: root.to_path_buf(), absolute, gitignore })
+  }
+
+  /// The changed file as a search under the root reports it. None for files a search skips: hidden ones, like
+  /// everything in `.git` and serpl's own temporary files, and those the root's `.gitignore` ignores.
+  fn search_path(&self, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(&self.absolute).ok()?;
+    let is_hidden = relative.components().any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+    if relative.as_os_str().is_empty() || is_hidden {
+      return None;
+    }
+    if self.gitignore.matched_path_or_any_parents(path, path.is_dir()).is_ignore() {
+      return None;
+    }
+    Some(self.root.join(relative))
+  }
+}
+
+/// Watches the project roots, sending a `RefreshFiles` thunk with the files changed on disk. Watching stops when the
+/// returned watcher is dropped.
+pub fn watch(roots: &[PathBuf], command_tx: UnboundedSender<AppAction>) -> notify::Result<RecommendedWatcher> {
+  let watched_roots =
+    roots.iter().map(|root| WatchedRoot::new(root)).collect::<std::io::Result<Vec<_>>>().map_err(notify::Error::io)?;
+
+  let (paths_tx, mut paths_rx) = mpsc::unbounded_channel();
+  let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+    match event {
+      Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+        for path in event.paths {
+          let _ = paths_tx.send(path);
+        }
+      },
+      Ok(_) => {},
+      Err(e) => log::error!("File watcher error: {}", e),
+    }
+  })?;
+  for watched_root in &watched_roots {
+    watcher.watch(&watched_root.absolute, RecursiveMode::Recursive)?;
+  }
+
+  tokio::spawn(async move {
+    while let Some(path) = paths_rx.recv().await {
+      let mut changed = BTreeSet::from([path]);
+      tokio::time::sleep(DEBOUNCE_DURATION).await;
+      while let Ok(path) = paths_rx.try_recv() {
+        changed.insert(path);
+      }
+
+      let paths: Vec<PathBuf> = changed
+        .iter()
+        .filter(|path| !path.is_dir())
+        .filter_map(|path| watched_roots.iter().find_map(|watched_root| watched_root.search_path(path)))
+        .collect();
+      if !paths.is_empty() && command_tx.send(AppAction::Thunk(ThunkAction::RefreshFiles(paths))).is_err() {
+        break;
+      }
+    }
+  });
+  Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use pretty_assertions::assert_eq;
+
+  use super::*;
+
+  #[test]
+  fn test_search_path() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().to_path_buf();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join(".gitignore"), "target/\n*.log\n").unwrap();
+
+    let watched_root = WatchedRoot::new(&dir).unwrap();
+    let absolute = dir.canonicalize().unwrap();
+    assert_eq!(watched_root.search_path(&absolute.join("src/main.rs")), Some(dir.join("src/main.rs")));
+    assert_eq!(watched_root.search_path(&absolute.join("target/debug/main")), None);
+    assert_eq!(watched_root.search_path(&absolute.join("serpl.log")), None);
+    assert_eq!(watched_root.search_path(&absolute.join(".git/index")), None);
+    assert_eq!(watched_root.search_path(&absolute.join("src/.main.rs.serpl-1.tmp")), None);
+    assert_eq!(watched_root.search_path(Path::new("/elsewhere/main.rs")), None);
+  }
+}